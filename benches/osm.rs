@@ -1,15 +1,27 @@
 use criterion::{criterion_group, criterion_main, Criterion};
-use osm_pbf2json::{filter, objects, streets};
+use osm_pbf2json::filter::{CompiledFilter, Filter, MemberIndex};
+use osm_pbf2json::{filter, objects, streets, ObjectOptions};
+use osmpbfreader::{OsmObj, OsmPbfReader};
 use std::fs::File;
 
+const EXPRESSION: &str = "amenity~cafe|restaurant|bar+wheelchair,shop~bakery,\
+                          highway~residential|living_street+maxspeed<50,building+addr:*";
+
 pub fn process_bench(c: &mut Criterion) {
     let mut group = c.benchmark_group("alexanderplatz");
     group.sample_size(10);
-    let groups = filter::parse("amenity");
+    let groups = filter::parse("amenity").unwrap();
     group.bench_function("process", |b| {
         b.iter(|| {
             let file = File::open("./tests/data/alexanderplatz.pbf").unwrap();
-            objects(file, Some(&groups), false).unwrap();
+            objects(
+                file,
+                &ObjectOptions {
+                    groups: Some(&groups),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
         })
     });
     group.finish();
@@ -21,11 +33,65 @@ pub fn streets_bench(c: &mut Criterion) {
     group.bench_function("streets", |b| {
         b.iter(|| {
             let file = File::open("./tests/data/alexanderplatz.pbf").unwrap();
-            streets(file, None, None).unwrap();
+            streets(file, None, None, None).unwrap();
+        })
+    });
+    group.finish();
+}
+
+pub fn filter_bench(c: &mut Criterion) {
+    let file = File::open("./tests/data/alexanderplatz.pbf").unwrap();
+    let objs: Vec<OsmObj> = OsmPbfReader::new(file)
+        .par_iter()
+        .map(Result::unwrap)
+        .collect();
+    let groups = filter::parse(EXPRESSION).unwrap();
+    let compiled = CompiledFilter::new(&groups);
+    let index = MemberIndex::default();
+
+    let mut group = c.benchmark_group("filter");
+    group.bench_function("interpreted", |b| {
+        b.iter(|| {
+            objs.iter()
+                .filter(|obj| obj.filter_with_index(&groups, &index))
+                .count()
+        })
+    });
+    group.bench_function("compiled", |b| {
+        b.iter(|| {
+            objs.iter()
+                .filter(|obj| compiled.matches(obj, &index))
+                .count()
+        })
+    });
+    group.finish();
+}
+
+pub fn process_filter_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("alexanderplatz");
+    group.sample_size(10);
+    let groups = filter::parse(EXPRESSION).unwrap();
+    group.bench_function("process_filter", |b| {
+        b.iter(|| {
+            let file = File::open("./tests/data/alexanderplatz.pbf").unwrap();
+            objects(
+                file,
+                &ObjectOptions {
+                    groups: Some(&groups),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
         })
     });
     group.finish();
 }
 
-criterion_group!(benches, process_bench, streets_bench);
+criterion_group!(
+    benches,
+    process_bench,
+    process_filter_bench,
+    streets_bench,
+    filter_bench
+);
 criterion_main!(benches);
@@ -0,0 +1,119 @@
+use super::geo::Location;
+use super::items::osm::{GeoInfo, Object};
+use super::items::Address;
+use osmpbfreader::objects::Tags;
+
+fn get_value(tags: &Tags, key: &str) -> Option<String> {
+    let value = tags.get(key)?.trim();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+/// A point for the address, the location of nodes and for shapes a point on their surface, as
+/// the centroid might lie within a neighbouring building
+fn get_location(geo_info: &GeoInfo) -> Option<Location> {
+    match geo_info {
+        GeoInfo::Point { lon, lat } => Some(Location {
+            lat: *lat,
+            lon: *lon,
+        }),
+        GeoInfo::Shape {
+            point_on_surface,
+            centroid,
+            ..
+        } => point_on_surface
+            .as_ref()
+            .or(centroid.as_ref())
+            .map(|loc| Location {
+                lat: loc.lat,
+                lon: loc.lon,
+            }),
+    }
+}
+
+/// Address of an object with an `addr:housenumber` or `addr:housename`, several house numbers
+/// can be given separated by semicolons
+pub fn extract_address(object: &Object) -> Option<Address> {
+    let tags = object.tags();
+    let housenumbers: Vec<String> = get_value(tags, "addr:housenumber")
+        .map(|numbers| {
+            numbers
+                .split(';')
+                .map(str::trim)
+                .filter(|number| !number.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default();
+    let housename = get_value(tags, "addr:housename");
+    if housenumbers.is_empty() && housename.is_none() {
+        return None;
+    }
+    let address = Address {
+        id: object.id(),
+        osm_type: object.osm_type(),
+        street: get_value(tags, "addr:street"),
+        place: get_value(tags, "addr:place"),
+        housenumbers,
+        housename,
+        postcode: get_value(tags, "addr:postcode"),
+        city: get_value(tags, "addr:city"),
+        location: get_location(object.geo_info()),
+    };
+    Some(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_helpers::create_tags;
+    use super::*;
+
+    fn create_object(pairs: &[(&str, &str)]) -> Object {
+        let tags = create_tags(pairs);
+        let geo_info = GeoInfo::Point {
+            lon: 13.4,
+            lat: 52.5,
+        };
+        Object::new(1, "node", tags, geo_info)
+    }
+
+    #[test]
+    fn extract_addresses() {
+        let object = create_object(&[
+            ("addr:street", " Karl-Marx-Allee "),
+            ("addr:housenumber", "1; 3 ;;5a"),
+            ("addr:postcode", "10178"),
+            ("addr:city", "Berlin"),
+            ("name", "Haus"),
+        ]);
+        let address = extract_address(&object).unwrap();
+        assert_eq!(address.street.as_deref(), Some("Karl-Marx-Allee"));
+        assert_eq!(address.housenumbers, vec!["1", "3", "5a"]);
+        assert_eq!(address.housename, None);
+        assert_eq!(address.postcode.as_deref(), Some("10178"));
+        assert_eq!(address.city.as_deref(), Some("Berlin"));
+        assert_eq!(
+            address.location.unwrap(),
+            Location {
+                lat: 52.5,
+                lon: 13.4
+            }
+        );
+
+        let object = create_object(&[("addr:place", "Museumsinsel"), ("addr:housename", "Bode")]);
+        let address = extract_address(&object).unwrap();
+        assert_eq!(address.street, None);
+        assert_eq!(address.place.as_deref(), Some("Museumsinsel"));
+        assert!(address.housenumbers.is_empty());
+        assert_eq!(address.housename.as_deref(), Some("Bode"));
+
+        let object = create_object(&[
+            ("addr:street", "Karl-Marx-Allee"),
+            ("addr:housenumber", " "),
+        ]);
+        assert!(extract_address(&object).is_none());
+    }
+}
@@ -1,135 +1,1444 @@
-use osmpbfreader::objects::{OsmObj, Tags};
+use itertools::Either;
+use osmpbfreader::objects::{NodeId, OsmId, OsmObj, Relation, RelationId, Tags, WayId};
+use regex::Regex;
+use serde::{de, Deserialize, Deserializer};
 use smartstring::alias::String;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use unicode_normalization::UnicodeNormalization;
 
-#[derive(PartialEq, Debug, Clone)]
+mod compiled;
+mod profile;
+mod projection;
+
+pub use compiled::CompiledFilter;
+pub use profile::Profiles;
+pub use projection::{parse_keys, parse_renames, TagProjection};
+
+/// A compiled regular expression for matching tag values
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "std::string::String")]
+pub struct Pattern(Box<Regex>);
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        let regex = Regex::new(pattern)?;
+        Ok(Pattern(Box::new(regex)))
+    }
+
+    pub fn is_match(&self, value: &str) -> bool {
+        self.0.is_match(value)
+    }
+}
+
+impl Pattern {
+    /// A case-insensitive variant of the pattern for matching normalized values
+    ///
+    /// Only the Unicode normalization is applied to the pattern itself, lowercasing it would
+    /// change the meaning of escapes like `\D` or `\S`.
+    fn normalized(&self) -> Self {
+        let source = self.0.as_str();
+        let composed: std::string::String = source.nfc().collect();
+        // composing might join an escaped character with a following combining mark, in which
+        // case the pattern is kept as is
+        Pattern::new(&format!("(?i){}", composed))
+            .or_else(|_| Pattern::new(&format!("(?i){}", source)))
+            .expect("a valid pattern remains valid when matched case-insensitively")
+    }
+}
+
+impl TryFrom<std::string::String> for Pattern {
+    type Error = regex::Error;
+
+    fn try_from(pattern: std::string::String) -> Result<Self, Self::Error> {
+        Pattern::new(&pattern)
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+/// A tag key, either stated explicitly or as a wildcard with a single `*` (e.g. `addr:*`)
+#[derive(PartialEq, Debug, Clone, Deserialize)]
+#[serde(try_from = "std::string::String")]
+pub enum Key {
+    Exact(String),
+    Wildcard { prefix: String, suffix: String },
+}
+
+impl Key {
+    fn matches(&self, key: &str) -> bool {
+        match self {
+            Key::Exact(exact) => exact == key,
+            Key::Wildcard { prefix, suffix } => {
+                key.len() >= prefix.len() + suffix.len()
+                    && key.starts_with(prefix.as_str())
+                    && key.ends_with(suffix.as_str())
+            }
+        }
+    }
+
+    /// Values of all tags with a matching key, tags are sorted by key so a wildcard's
+    /// prefix narrows down the scanned range.
+    fn values<'a>(&'a self, tags: &'a Tags) -> impl Iterator<Item = &'a String> + 'a {
+        match self {
+            Key::Exact(key) => Either::Left(tags.get(key.as_str()).into_iter()),
+            Key::Wildcard { prefix, .. } => Either::Right(
+                tags.iter()
+                    .skip_while(move |(key, _)| key.as_str() < prefix.as_str())
+                    .take_while(move |(key, _)| key.starts_with(prefix.as_str()))
+                    .filter(move |(key, _)| self.matches(key))
+                    .map(|(_, value)| value),
+            ),
+        }
+    }
+}
+
+impl From<&str> for Key {
+    fn from(key: &str) -> Self {
+        Key::Exact(key.into())
+    }
+}
+
+impl TryFrom<std::string::String> for Key {
+    type Error = &'static str;
+
+    fn try_from(key: std::string::String) -> Result<Self, Self::Error> {
+        let mut parts = key.splitn(2, '*');
+        let prefix = parts.next().unwrap_or_default().into();
+        match parts.next() {
+            Some(suffix) if suffix.contains('*') => Err("keys may contain a single wildcard"),
+            Some(suffix) => Ok(Key::Wildcard {
+                prefix,
+                suffix: suffix.into(),
+            }),
+            None => Ok(Key::Exact(prefix)),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy, Deserialize)]
+pub enum Comparison {
+    #[serde(rename = "<")]
+    Less,
+    #[serde(rename = "<=")]
+    LessOrEqual,
+    #[serde(rename = ">")]
+    Greater,
+    #[serde(rename = ">=")]
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    fn holds(self, lhs: f64, rhs: f64) -> bool {
+        match self {
+            Comparison::Less => lhs < rhs,
+            Comparison::LessOrEqual => lhs <= rhs,
+            Comparison::Greater => lhs > rhs,
+            Comparison::GreaterOrEqual => lhs >= rhs,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ElementType {
+    Node,
+    Way,
+    Relation,
+}
+
+impl ElementType {
+    fn matches(self, obj: &OsmObj) -> bool {
+        match self {
+            ElementType::Node => obj.is_node(),
+            ElementType::Way => obj.is_way(),
+            ElementType::Relation => obj.is_relation(),
+        }
+    }
+}
+
+/// A property computed from an element's geometry
+#[derive(PartialEq, Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Measure {
+    Area,
+    Length,
+}
+
+impl Measure {
+    fn of(self, measures: &Measures) -> Option<f64> {
+        match self {
+            Measure::Area => measures.area,
+            Measure::Length => measures.length,
+        }
+    }
+}
+
+/// Spherical approximation of the area (m²) of closed and length (m) of linear geometries
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct Measures {
+    pub area: Option<f64>,
+    pub length: Option<f64>,
+}
+
+/// A single condition on an element
+///
+/// Conditions deserialize from a map with the variant's name in snake case, e.g.
+/// `{"value_match": ["amenity", "cafe"]}` or `{"not": {"tag_presence": "addr:*"}}`. Keys and
+/// patterns are given as strings, ids as a string in the format of `parse_ids`. The condition
+/// of a `normalized` entry is passed through `Condition::normalized`.
+#[derive(PartialEq, Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Condition {
-    TagPresence(String),
-    ValueMatch(String, String),
+    TagPresence(Key),
+    ValueMatch(Key, String),
+    ValueAlternatives(Key, Vec<String>),
+    RegexMatch(Key, Pattern),
+    NumericComparison(Key, Comparison, f64),
+    Geometry(Measure, Comparison, f64),
+    ElementType(ElementType),
+    Ids(#[serde(deserialize_with = "deserialize_ids")] BTreeSet<OsmId>),
+    MemberOf(Option<String>, Vec<Group>),
+    Any(Vec<Group>),
+    Normalized(#[serde(deserialize_with = "deserialize_normalized")] Box<Condition>),
+    Not(Box<Condition>),
 }
 
-impl Condition {
-    pub fn new(tag: &str, value: Option<&str>) -> Self {
-        if let Some(value) = value {
-            return Condition::ValueMatch(tag.into(), value.into());
-        }
-        Condition::TagPresence(tag.into())
+impl Condition {
+    pub fn new(tag: &str, value: Option<&str>) -> Self {
+        if let Some(value) = value {
+            return Condition::ValueMatch(tag.into(), value.into());
+        }
+        Condition::TagPresence(tag.into())
+    }
+
+    pub fn regex(tag: &str, pattern: &str) -> Result<Self, regex::Error> {
+        let pattern = Pattern::new(pattern)?;
+        Ok(Condition::RegexMatch(tag.into(), pattern))
+    }
+
+    pub fn negate(self) -> Self {
+        Condition::Not(Box::new(self))
+    }
+
+    /// Compare values of the condition case-insensitively and independent of their Unicode
+    /// normalization form. Conditions which do not compare values are left unchanged.
+    pub fn normalized(self) -> Self {
+        let condition = match self {
+            Condition::ValueMatch(key, value) => Condition::ValueMatch(key, normalize(&value)),
+            Condition::ValueAlternatives(key, values) => {
+                let values = values.iter().map(|value| normalize(value)).collect();
+                Condition::ValueAlternatives(key, values)
+            }
+            Condition::RegexMatch(key, pattern) => Condition::RegexMatch(key, pattern.normalized()),
+            Condition::Any(groups) => {
+                return Condition::Any(groups.into_iter().map(Group::normalized).collect())
+            }
+            Condition::MemberOf(role, groups) => {
+                let groups = groups.into_iter().map(Group::normalized).collect();
+                return Condition::MemberOf(role, groups);
+            }
+            Condition::Not(condition) => return condition.normalized().negate(),
+            condition => return condition,
+        };
+        Condition::Normalized(Box::new(condition))
+    }
+
+    /// Whether evaluating the condition requires a `MemberIndex`
+    pub fn uses_membership(&self) -> bool {
+        match self {
+            Condition::MemberOf(..) => true,
+            Condition::Any(groups) => groups.iter().any(Group::uses_membership),
+            Condition::Normalized(condition) | Condition::Not(condition) => {
+                condition.uses_membership()
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether evaluating the condition requires the `Measures` of an element's geometry
+    ///
+    /// Geometry conditions within membership conditions are not evaluated.
+    pub fn uses_geometry(&self) -> bool {
+        match self {
+            Condition::Geometry(..) => true,
+            Condition::Any(groups) => groups.iter().any(Group::uses_geometry),
+            Condition::Normalized(condition) | Condition::Not(condition) => {
+                condition.uses_geometry()
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Conditions which all need to hold
+#[derive(PartialEq, Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Group {
+    pub conditions: Vec<Condition>,
+}
+
+/// A group with the label given to it, to tell groups apart in the output
+pub type LabeledGroup = (Option<std::string::String>, Group);
+
+impl Group {
+    /// Apply `Condition::normalized` to all conditions of the group
+    pub fn normalized(self) -> Self {
+        let conditions = self
+            .conditions
+            .into_iter()
+            .map(Condition::normalized)
+            .collect();
+        Group { conditions }
+    }
+
+    /// Whether evaluating the group requires a `MemberIndex`
+    pub fn uses_membership(&self) -> bool {
+        self.conditions.iter().any(Condition::uses_membership)
+    }
+
+    /// Whether evaluating the group requires the `Measures` of an element's geometry
+    pub fn uses_geometry(&self) -> bool {
+        self.conditions.iter().any(Condition::uses_geometry)
+    }
+}
+
+/// Reverse index from OSM elements to the relations they are members of
+///
+/// Membership conditions can not be decided by looking at an element alone, the relations
+/// referring to it need to be known before it is filtered.
+#[derive(Default)]
+pub struct MemberIndex {
+    parents: BTreeMap<OsmId, Vec<(String, RelationId)>>,
+    relations: BTreeMap<RelationId, OsmObj>,
+}
+
+impl MemberIndex {
+    pub fn new(relations: impl IntoIterator<Item = Relation>) -> Self {
+        let mut index = MemberIndex::default();
+        for relation in relations {
+            for member in &relation.refs {
+                let parent = (member.role.clone(), relation.id);
+                index.parents.entry(member.member).or_default().push(parent);
+            }
+            index.relations.insert(relation.id, relation.into());
+        }
+        index
+    }
+
+    fn parents(&self, id: OsmId) -> impl Iterator<Item = (&String, &OsmObj)> {
+        self.parents
+            .get(&id)
+            .into_iter()
+            .flatten()
+            .filter_map(move |(role, relation_id)| Some((role, self.relations.get(relation_id)?)))
+    }
+}
+
+/// Fold the case of a value and bring it into Unicode normalization form C
+fn normalize(value: &str) -> String {
+    value.to_lowercase().nfc().collect()
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum ParseErrorKind {
+    UnexpectedCharacter(char),
+    UnexpectedEnd,
+    ExpectedKey,
+    ExpectedValue,
+    UnterminatedString,
+    UnterminatedPattern,
+    InvalidPattern(std::string::String),
+    InvalidNumber(std::string::String),
+    InvalidId(std::string::String),
+    UnknownMeasure(std::string::String),
+}
+
+/// An error in a filter expression, `position` is the character offset at which it occurred
+#[derive(PartialEq, Debug, Clone)]
+pub struct ParseError {
+    pub position: usize,
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected character '{}'", c),
+            ParseErrorKind::UnexpectedEnd => write!(f, "unexpected end of expression"),
+            ParseErrorKind::ExpectedKey => write!(f, "expected a key"),
+            ParseErrorKind::ExpectedValue => write!(f, "expected a value"),
+            ParseErrorKind::UnterminatedString => write!(f, "unterminated string"),
+            ParseErrorKind::UnterminatedPattern => write!(f, "unterminated pattern"),
+            ParseErrorKind::InvalidPattern(message) => write!(f, "invalid pattern: {}", message),
+            ParseErrorKind::InvalidNumber(value) => write!(f, "invalid number '{}'", value),
+            ParseErrorKind::InvalidId(value) => write!(f, "invalid id '{}'", value),
+            ParseErrorKind::UnknownMeasure(name) => write!(f, "unknown measure '{}'", name),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.kind, self.position)
+    }
+}
+
+impl Error for ParseError {}
+
+/// Parse the leading number of a tag value, ignoring a trailing unit (e.g. `50 mph`)
+fn parse_number(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let end = value
+        .char_indices()
+        .find(|&(idx, c)| !(c.is_ascii_digit() || c == '.' || (idx == 0 && c == '-')))
+        .map_or(value.len(), |(idx, _)| idx);
+    value[..end].parse().ok()
+}
+
+const KEY_DELIMITERS: &[char] = &['~', '!', '<', '>', ',', '+', '(', ')', '|', '"'];
+const MEMBERSHIP: &str = "member";
+const ELEMENT_TYPES: &[(&str, ElementType)] = &[
+    ("node:", ElementType::Node),
+    ("way:", ElementType::Way),
+    ("rel:", ElementType::Relation),
+    ("relation:", ElementType::Relation),
+];
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    /// Number of enclosing parentheses at the current position
+    depth: usize,
+}
+
+impl Parser {
+    fn new(expression: &str) -> Self {
+        Parser {
+            chars: expression.chars().collect(),
+            position: 0,
+            depth: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.position + offset).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn starts_with(&self, prefix: &str) -> bool {
+        prefix
+            .chars()
+            .enumerate()
+            .all(|(offset, c)| self.peek_at(offset) == Some(c))
+    }
+
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            position: self.position,
+            kind,
+        }
+    }
+
+    fn unexpected(&self) -> ParseError {
+        match self.peek() {
+            Some(c) => self.error(ParseErrorKind::UnexpectedCharacter(c)),
+            None => self.error(ParseErrorKind::UnexpectedEnd),
+        }
+    }
+
+    fn parse_expression(&mut self) -> Result<Vec<LabeledGroup>, ParseError> {
+        let mut groups = vec![self.parse_group()?];
+        while self.eat(',') {
+            groups.push(self.parse_group()?);
+        }
+        Ok(groups)
+    }
+
+    fn parse_group(&mut self) -> Result<LabeledGroup, ParseError> {
+        let mut label = None;
+        if self.eat('[') {
+            let position = self.position;
+            let value = self.parse_string(&[']'])?;
+            if value.is_empty() {
+                return Err(ParseError {
+                    position,
+                    kind: ParseErrorKind::ExpectedValue,
+                });
+            }
+            if !self.eat(']') {
+                return Err(self.unexpected());
+            }
+            label = Some(value.to_string());
+        }
+        let mut conditions = vec![];
+        for (prefix, element_type) in ELEMENT_TYPES {
+            if self.starts_with(prefix) {
+                self.position += prefix.chars().count();
+                conditions.push(Condition::ElementType(*element_type));
+                break;
+            }
+        }
+        self.parse_term(&mut conditions)?;
+        while self.eat('+') {
+            self.parse_term(&mut conditions)?;
+        }
+        Ok((label, Group { conditions }))
+    }
+
+    fn parse_term(&mut self, conditions: &mut Vec<Condition>) -> Result<(), ParseError> {
+        let negated = self.peek() == Some('!') && self.peek_at(1) == Some('(');
+        if negated {
+            self.position += 1;
+        }
+        if !self.eat('(') {
+            conditions.push(self.parse_condition()?);
+            return Ok(());
+        }
+        let mut groups = self.parse_nested_expression()?;
+        match (negated, groups.len()) {
+            (false, 1) => conditions.append(&mut groups[0].conditions),
+            (false, _) => conditions.push(Condition::Any(groups)),
+            (true, _) => conditions.push(Condition::Any(groups).negate()),
+        }
+        Ok(())
+    }
+
+    fn parse_condition(&mut self) -> Result<Condition, ParseError> {
+        let negated = self.eat('!');
+        let membership_start = self.peek_at(MEMBERSHIP.chars().count());
+        if self.starts_with(MEMBERSHIP) && matches!(membership_start, Some('(') | Some('[')) {
+            let condition = self.parse_membership()?;
+            return Ok(if negated {
+                condition.negate()
+            } else {
+                condition
+            });
+        }
+        if self.peek() == Some('@') {
+            let condition = self.parse_measure()?;
+            return Ok(if negated {
+                condition.negate()
+            } else {
+                condition
+            });
+        }
+        let key_position = self.position;
+        let key = self.parse_key()?;
+        if key == Key::Exact("".into()) {
+            return Err(ParseError {
+                position: key_position,
+                kind: ParseErrorKind::ExpectedKey,
+            });
+        }
+        let condition = match self.peek() {
+            None | Some(',') | Some('+') | Some(')') => Condition::TagPresence(key),
+            Some('~') if self.peek_at(1) == Some('*') => {
+                self.position += 2;
+                self.parse_value(key)?.normalized()
+            }
+            Some('~') => {
+                self.position += 1;
+                self.parse_value(key)?
+            }
+            Some('!') if self.peek_at(1) == Some('~') && self.peek_at(2) == Some('*') => {
+                self.position += 3;
+                self.parse_value(key)?.normalized().negate()
+            }
+            Some('!') if self.peek_at(1) == Some('~') => {
+                self.position += 2;
+                self.parse_value(key)?.negate()
+            }
+            Some('<') | Some('>') => self.parse_comparison(key)?,
+            Some(_) => return Err(self.unexpected()),
+        };
+        if negated {
+            return Ok(condition.negate());
+        }
+        Ok(condition)
+    }
+
+    fn parse_membership(&mut self) -> Result<Condition, ParseError> {
+        self.position += MEMBERSHIP.chars().count();
+        let mut role = None;
+        if self.eat('[') {
+            role = Some(self.parse_string(&[']'])?);
+            if !self.eat(']') {
+                return Err(self.unexpected());
+            }
+        }
+        if !self.eat('(') {
+            return Err(self.unexpected());
+        }
+        let groups = self.parse_nested_expression()?;
+        Ok(Condition::MemberOf(role, groups))
+    }
+
+    /// Parse an expression following an opening parenthesis, up to the closing one, labels of
+    /// nested groups are ignored
+    fn parse_nested_expression(&mut self) -> Result<Vec<Group>, ParseError> {
+        self.depth += 1;
+        let groups = self.parse_expression()?;
+        if !self.eat(')') {
+            return Err(self.unexpected());
+        }
+        self.depth -= 1;
+        Ok(groups.into_iter().map(|(_, group)| group).collect())
+    }
+
+    fn parse_value(&mut self, key: Key) -> Result<Condition, ParseError> {
+        if self.peek() == Some('/') {
+            return self.parse_pattern(key);
+        }
+        let mut values = vec![self.parse_value_string()?];
+        while self.eat('|') {
+            values.push(self.parse_value_string()?);
+        }
+        if values.len() == 1 {
+            return Ok(Condition::ValueMatch(key, values.remove(0)));
+        }
+        Ok(Condition::ValueAlternatives(key, values))
+    }
+
+    fn parse_measure(&mut self) -> Result<Condition, ParseError> {
+        let start = self.position;
+        self.position += 1;
+        let name = self.parse_string(KEY_DELIMITERS)?;
+        let measure = match name.as_str() {
+            "area" => Measure::Area,
+            "length" => Measure::Length,
+            _ => {
+                return Err(ParseError {
+                    position: start,
+                    kind: ParseErrorKind::UnknownMeasure(name.into()),
+                })
+            }
+        };
+        if !matches!(self.peek(), Some('<') | Some('>')) {
+            return Err(self.unexpected());
+        }
+        let (comparison, number) = self.parse_comparison_operands()?;
+        Ok(Condition::Geometry(measure, comparison, number))
+    }
+
+    fn parse_comparison(&mut self, key: Key) -> Result<Condition, ParseError> {
+        let (comparison, number) = self.parse_comparison_operands()?;
+        Ok(Condition::NumericComparison(key, comparison, number))
+    }
+
+    fn parse_comparison_operands(&mut self) -> Result<(Comparison, f64), ParseError> {
+        let comparison = match (self.peek(), self.peek_at(1)) {
+            (Some('<'), Some('=')) => Comparison::LessOrEqual,
+            (Some('>'), Some('=')) => Comparison::GreaterOrEqual,
+            (Some('<'), _) => Comparison::Less,
+            _ => Comparison::Greater,
+        };
+        self.position += match comparison {
+            Comparison::LessOrEqual | Comparison::GreaterOrEqual => 2,
+            Comparison::Less | Comparison::Greater => 1,
+        };
+        let value_position = self.position;
+        let value = self.parse_required_string()?;
+        let number = value.trim().parse().ok().filter(|n: &f64| n.is_finite());
+        let number = number.ok_or_else(|| ParseError {
+            position: value_position,
+            kind: ParseErrorKind::InvalidNumber(value.into()),
+        })?;
+        Ok((comparison, number))
+    }
+
+    fn parse_pattern(&mut self, key: Key) -> Result<Condition, ParseError> {
+        let start = self.position;
+        self.position += 1;
+        let mut pattern = std::string::String::new();
+        loop {
+            match self.peek() {
+                None => {
+                    return Err(ParseError {
+                        position: start,
+                        kind: ParseErrorKind::UnterminatedPattern,
+                    })
+                }
+                Some('/') => break,
+                Some('\\') if self.peek_at(1) == Some('/') => {
+                    pattern.push('/');
+                    self.position += 1;
+                }
+                Some(c) => pattern.push(c),
+            }
+            self.position += 1;
+        }
+        self.position += 1;
+        let pattern = Pattern::new(&pattern).map_err(|error| ParseError {
+            position: start,
+            kind: ParseErrorKind::InvalidPattern(error.to_string()),
+        })?;
+        Ok(Condition::RegexMatch(key, pattern))
+    }
+
+    fn parse_key(&mut self) -> Result<Key, ParseError> {
+        if self.peek() == Some('"') {
+            return Ok(Key::Exact(self.parse_quoted_string()?));
+        }
+        let mut prefix = String::new();
+        let mut suffix: Option<String> = None;
+        while let Some(c) = self.peek() {
+            if KEY_DELIMITERS.contains(&c) {
+                break;
+            }
+            if c == '*' {
+                if suffix.is_some() {
+                    return Err(self.unexpected());
+                }
+                suffix = Some(String::new());
+                self.position += 1;
+                continue;
+            }
+            if c == '\\' {
+                self.position += 1;
+            }
+            let c = self.peek().ok_or_else(|| self.unexpected())?;
+            match suffix.as_mut() {
+                Some(suffix) => suffix.push(c),
+                None => prefix.push(c),
+            }
+            self.position += 1;
+        }
+        let key = match suffix {
+            Some(suffix) => Key::Wildcard { prefix, suffix },
+            None => Key::Exact(prefix),
+        };
+        Ok(key)
+    }
+
+    fn parse_required_string(&mut self) -> Result<String, ParseError> {
+        let position = self.position;
+        let quoted = self.peek() == Some('"');
+        let value = self.parse_value_string()?;
+        if value.is_empty() && !quoted {
+            return Err(ParseError {
+                position,
+                kind: ParseErrorKind::ExpectedValue,
+            });
+        }
+        Ok(value)
+    }
+
+    /// A value extends up to the next `,`, `+` or `|`, or the `)` closing an enclosing group,
+    /// other characters only have a special meaning at its start
+    fn parse_value_string(&mut self) -> Result<String, ParseError> {
+        if self.peek() == Some('"') {
+            return self.parse_quoted_string();
+        }
+        let mut string = String::new();
+        let mut open = 0;
+        while let Some(c) = self.peek() {
+            match c {
+                ',' | '+' | '|' => break,
+                ')' if open == 0 && self.depth > 0 => break,
+                '(' => open += 1,
+                ')' if open > 0 => open -= 1,
+                _ => {}
+            }
+            if c == '\\' {
+                self.position += 1;
+                let escaped = self.peek().ok_or_else(|| self.unexpected())?;
+                string.push(escaped);
+            } else {
+                string.push(c);
+            }
+            self.position += 1;
+        }
+        Ok(string)
+    }
+
+    fn parse_string(&mut self, delimiters: &[char]) -> Result<String, ParseError> {
+        if self.peek() == Some('"') {
+            return self.parse_quoted_string();
+        }
+        let mut string = String::new();
+        while let Some(c) = self.peek() {
+            if delimiters.contains(&c) {
+                break;
+            }
+            if c == '\\' {
+                self.position += 1;
+                let escaped = self.peek().ok_or_else(|| self.unexpected())?;
+                string.push(escaped);
+            } else {
+                string.push(c);
+            }
+            self.position += 1;
+        }
+        Ok(string)
+    }
+
+    fn parse_quoted_string(&mut self) -> Result<String, ParseError> {
+        let start = self.position;
+        self.position += 1;
+        let mut string = String::new();
+        loop {
+            let c = self.peek().ok_or(ParseError {
+                position: start,
+                kind: ParseErrorKind::UnterminatedString,
+            })?;
+            self.position += 1;
+            match c {
+                '"' => return Ok(string),
+                '\\' => {
+                    let escaped = self.peek().ok_or(ParseError {
+                        position: start,
+                        kind: ParseErrorKind::UnterminatedString,
+                    })?;
+                    string.push(escaped);
+                    self.position += 1;
+                }
+                c => string.push(c),
+            }
+        }
+    }
+}
+
+/// Parse an expression into a filter groups
+///
+/// Stating a key (`amenity`), will pick all entities which are tagged using that key.
+/// To further narrow down the results, a specific value can be given using a `~` field
+/// separator (`amenity~fountain`). To check the presence of multiple tags for the same
+/// entity, statements can be combined using the `+` operator (`'amenity~fountain+tourism'`).
+/// Finally, options can be specified by concatenating groups of statements with `,`
+/// (`amenity~fountain+tourism,amenity~townhall`). If an entity matches the criteria of
+/// either group it will be included in the output.
+///
+/// Parentheses can be used to nest groups (`(amenity~cafe,shop~bakery)+wheelchair~yes`).
+/// Keys and values containing special characters can be written in double quotes
+/// (`name~"Café, Bar"`) or escaped using a backslash (`name~Café\, Bar`).
+///
+/// A value extends up to the next `,`, `+` or `|`, or the `)` closing a nested group. Other
+/// characters are taken literally (`name~Café (Mitte)`, `note~a~b`), only double quotes and
+/// slashes at the start of a value have a special meaning. A value can be empty (`amenity~`).
+///
+/// A group can be restricted to an element type by prefixing it with `node:`, `way:` or
+/// `rel:` (`node:amenity,way:highway~residential`).
+///
+/// Keys can contain a single `*` wildcard to match several tags at once (`addr:*`,
+/// `*:wikidata`). A condition on a wildcard key holds if it holds for any of the matching
+/// tags (`name:*~/^Berlin/`).
+///
+/// Alternative values for a key can be separated by `|` (`amenity~cafe|restaurant|bar`).
+///
+/// A value enclosed in slashes is treated as a regular expression (`name~/^Berlin/`).
+///
+/// Conditions can be negated: `!addr:housenumber` selects entities without that key, while
+/// `amenity!~parking` selects entities which are not tagged with that value (including
+/// entities without an `amenity` key). A nested expression can be negated as well
+/// (`building+!(addr:street,addr:place)`).
+///
+/// Using `~*` instead of `~` compares values case-insensitively and independent of their
+/// Unicode normalization form (`wheelchair~*yes`, `name~*/^straße/`).
+///
+/// Numeric values can be compared using `<`, `<=`, `>` and `>=` (`capacity>=10`). Tag values
+/// are read up to the first non-numeric character, so units are ignored (`maxspeed=50 mph`
+/// matches `maxspeed<60`). Values which do not start with a number never match a comparison.
+/// The given number has to be a valid number as a whole (`capacity>1e3`).
+///
+/// The area (m²) of closed ways and multipolygons and the length (m) of linear ways, as
+/// approximated on a sphere, can be compared using `@area` and `@length` (`building+@area>=50`,
+/// `highway~footway+@length<5`). Other relations have neither.
+/// Geometry conditions are only evaluated by `Filter::filter_with_measures`, otherwise they
+/// are assumed to hold.
+///
+/// Groups can be labeled by prefixing them with a name in brackets
+/// (`[food]amenity~cafe|restaurant,[transport]public_transport`), so the groups an entity
+/// matched can be told apart in the output. The labels are only kept by `parse_labeled`,
+/// labels of nested groups are ignored.
+///
+/// `member(...)` selects entities which are members of a relation matching the nested
+/// expression (`way:member(type~route+route~bus)`). A role can be required by stating it in
+/// brackets (`node:member[stop](type~route)`). Membership conditions are only evaluated by
+/// `Filter::filter_with_index`.
+///
+/// An error is returned for malformed expressions, stating the position of the problem.
+///
+/// # Example
+///
+/// ```
+/// use osm_pbf2json::filter::parse;
+///
+/// let groups = parse("amenity~fountain+tourism,amenity~townhall".into()).unwrap();
+/// assert_eq!(groups.len(), 2);
+/// let group = &groups[0];
+/// assert_eq!(group.conditions.len(), 2);
+///
+/// let error = parse("amenity+~fountain").unwrap_err();
+/// assert_eq!(error.position, 8);
+/// ```
+pub fn parse(selector_str: &str) -> Result<Vec<Group>, ParseError> {
+    let groups = parse_labeled(selector_str)?;
+    Ok(groups.into_iter().map(|(_, group)| group).collect())
+}
+
+/// Parse an expression like `parse`, keeping the labels of the groups
+///
+/// # Example
+///
+/// ```
+/// use osm_pbf2json::filter::parse_labeled;
+///
+/// let groups = parse_labeled("[food]amenity~cafe,shop".into()).unwrap();
+/// assert_eq!(groups[0].0.as_deref(), Some("food"));
+/// assert_eq!(groups[1].0, None);
+/// ```
+pub fn parse_labeled(selector_str: &str) -> Result<Vec<LabeledGroup>, ParseError> {
+    let mut parser = Parser::new(selector_str);
+    let groups = parser.parse_expression()?;
+    if parser.peek().is_some() {
+        return Err(parser.unexpected());
+    }
+    Ok(groups)
+}
+
+/// Parse a list of OSM ids, separated by commas or whitespace
+///
+/// Ids are prefixed with the element type: `n123` refers to a node, `w456` to a way and
+/// `r789` to a relation. Negative ids, which editors like JOSM assign to elements not uploaded
+/// yet, can be given as well (`n-5`).
+pub fn parse_ids(ids_str: &str) -> Result<BTreeSet<OsmId>, ParseError> {
+    let mut ids = BTreeSet::new();
+    let chars: Vec<char> = ids_str.chars().collect();
+    let is_separator = |c: &char| *c == ',' || c.is_whitespace();
+    let mut position = 0;
+    while position < chars.len() {
+        if is_separator(&chars[position]) {
+            position += 1;
+            continue;
+        }
+        let token: std::string::String = chars[position..]
+            .iter()
+            .take_while(|c| !is_separator(c))
+            .collect();
+        let invalid = || ParseError {
+            position,
+            kind: ParseErrorKind::InvalidId(token.clone()),
+        };
+        let mut token_chars = token.chars();
+        let element_type = token_chars.next().ok_or_else(invalid)?;
+        let number = token_chars.as_str().parse().map_err(|_| invalid())?;
+        let id = match element_type {
+            'n' => OsmId::Node(NodeId(number)),
+            'w' => OsmId::Way(WayId(number)),
+            'r' => OsmId::Relation(RelationId(number)),
+            _ => return Err(invalid()),
+        };
+        ids.insert(id);
+        position += token.chars().count();
+    }
+    Ok(ids)
+}
+
+fn deserialize_ids<'de, D>(deserializer: D) -> Result<BTreeSet<OsmId>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let ids = std::string::String::deserialize(deserializer)?;
+    parse_ids(&ids).map_err(de::Error::custom)
+}
+
+fn deserialize_normalized<'de, D>(deserializer: D) -> Result<Box<Condition>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let condition = Condition::deserialize(deserializer)?;
+    match condition.normalized() {
+        Condition::Normalized(condition) => Ok(condition),
+        condition => Ok(Box::new(condition)),
+    }
+}
+
+/// Information beyond an element's own tags, some conditions depend on
+///
+/// Without `measures`, geometry conditions (and negations of them) are assumed to hold, so
+/// filtering yields a superset of the elements which match once their geometry is known.
+struct Context<'a> {
+    index: &'a MemberIndex,
+    measures: Option<&'a Measures>,
+}
+
+fn check_condition(obj: &OsmObj, condition: &Condition, ctx: &Context) -> bool {
+    let tags = obj.tags();
+    match condition {
+        Condition::TagPresence(key) => key.values(tags).next().is_some(),
+        Condition::ValueMatch(key, value) => key.values(tags).any(|v| v == value),
+        Condition::ValueAlternatives(key, values) => key
+            .values(tags)
+            .any(|value| values.iter().any(|v| v == value)),
+        Condition::RegexMatch(key, pattern) => {
+            key.values(tags).any(|value| pattern.is_match(value))
+        }
+        Condition::NumericComparison(key, comparison, number) => key
+            .values(tags)
+            .filter_map(|value| parse_number(value))
+            .any(|value| comparison.holds(value, *number)),
+        Condition::Geometry(measure, comparison, number) => match ctx.measures {
+            Some(measures) => measure
+                .of(measures)
+                .is_some_and(|value| comparison.holds(value, *number)),
+            None => true,
+        },
+        Condition::ElementType(element_type) => element_type.matches(obj),
+        Condition::Ids(ids) => ids.contains(&obj.id()),
+        Condition::MemberOf(role, groups) => {
+            let relation_ctx = Context {
+                index: ctx.index,
+                measures: None,
+            };
+            ctx.index.parents(obj.id()).any(|(member_role, relation)| {
+                role.as_ref().is_none_or(|role| role == member_role)
+                    && groups
+                        .iter()
+                        .any(|g| check_group(relation, g, &relation_ctx))
+            })
+        }
+        Condition::Any(groups) => groups.iter().any(|g| check_group(obj, g, ctx)),
+        Condition::Normalized(condition) => check_normalized(obj, condition, ctx),
+        Condition::Not(condition) if ctx.measures.is_none() && condition.uses_geometry() => true,
+        Condition::Not(condition) => !check_condition(obj, condition, ctx),
+    }
+}
+
+fn check_normalized(obj: &OsmObj, condition: &Condition, ctx: &Context) -> bool {
+    let tags = obj.tags();
+    match condition {
+        Condition::ValueMatch(key, expected) => {
+            key.values(tags).any(|value| &normalize(value) == expected)
+        }
+        Condition::ValueAlternatives(key, expected) => key
+            .values(tags)
+            .any(|value| expected.contains(&normalize(value))),
+        Condition::RegexMatch(key, pattern) => key
+            .values(tags)
+            .any(|value| pattern.is_match(&normalize(value))),
+        condition => check_condition(obj, condition, ctx),
+    }
+}
+
+fn check_group(obj: &OsmObj, group: &Group, ctx: &Context) -> bool {
+    group
+        .conditions
+        .iter()
+        .all(|c| check_condition(obj, c, ctx))
+}
+
+pub trait Filter {
+    fn filter(&self, groups: &[Group]) -> bool;
+
+    /// Evaluate membership conditions using the index, by default the same as `filter`
+    fn filter_with_index(&self, groups: &[Group], _index: &MemberIndex) -> bool {
+        self.filter(groups)
+    }
+
+    /// Evaluate geometry conditions using the measures, by default the same as
+    /// `filter_with_index`
+    fn filter_with_measures(
+        &self,
+        groups: &[Group],
+        index: &MemberIndex,
+        _measures: &Measures,
+    ) -> bool {
+        self.filter_with_index(groups, index)
+    }
+}
+
+impl Filter for OsmObj {
+    fn filter(&self, groups: &[Group]) -> bool {
+        self.filter_with_index(groups, &MemberIndex::default())
+    }
+
+    fn filter_with_index(&self, groups: &[Group], index: &MemberIndex) -> bool {
+        let ctx = Context {
+            index,
+            measures: None,
+        };
+        groups.iter().any(|c| check_group(self, c, &ctx))
+    }
+
+    fn filter_with_measures(
+        &self,
+        groups: &[Group],
+        index: &MemberIndex,
+        measures: &Measures,
+    ) -> bool {
+        let ctx = Context {
+            index,
+            measures: Some(measures),
+        };
+        groups.iter().any(|c| check_group(self, c, &ctx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use osmpbfreader::objects::{Node, Way};
+
+    fn new_node() -> Node {
+        let tags = Tags::new();
+        let id = NodeId(1);
+        Node {
+            id,
+            tags,
+            decimicro_lat: 0,
+            decimicro_lon: 0,
+        }
+    }
+
+    #[test]
+    fn filter_single_group() {
+        let condition = Condition::TagPresence("amenity".into());
+        let conditions = vec![condition];
+        let group = Group { conditions };
+
+        let node = new_node();
+        let obj = OsmObj::Node(node);
+
+        assert!(!obj.filter(&[group.clone()]));
+
+        let mut node = new_node();
+        node.tags.insert("amenity".into(), "theatre".into());
+        let obj = OsmObj::Node(node);
+
+        assert!(obj.filter(&[group]));
+    }
+
+    #[test]
+    fn filter_with_default_methods() {
+        struct Tagged(Tags);
+
+        impl Filter for Tagged {
+            fn filter(&self, groups: &[Group]) -> bool {
+                let obj = OsmObj::Node(Node {
+                    tags: self.0.clone(),
+                    ..new_node()
+                });
+                obj.filter(groups)
+            }
+        }
+
+        let mut tags = Tags::new();
+        tags.insert("amenity".into(), "cafe".into());
+        let tagged = Tagged(tags);
+        let groups = parse("amenity~cafe").unwrap();
+        let measures = Measures::default();
+        assert!(tagged.filter_with_index(&groups, &MemberIndex::default()));
+        assert!(tagged.filter_with_measures(&groups, &MemberIndex::default(), &measures));
+        assert!(!tagged.filter_with_index(&parse("shop").unwrap(), &MemberIndex::default()));
+    }
+
+    #[test]
+    fn filter_value_match() {
+        let condition = Condition::ValueMatch("amenity".into(), "theatre".into());
+        let conditions = vec![condition];
+        let group = Group { conditions };
+
+        let mut node = new_node();
+        node.tags.insert("amenity".into(), "theatre".into());
+        let obj = OsmObj::Node(node);
+        assert!(obj.filter(&[group.clone()]));
+
+        let mut node = new_node();
+        node.tags.insert("amenity".into(), "cinema".into());
+        let obj = OsmObj::Node(node);
+        assert!(!obj.filter(&[group]));
+    }
+
+    #[test]
+    fn filter_value_alternatives() {
+        let values = vec!["cafe".into(), "bar".into()];
+        let condition = Condition::ValueAlternatives("amenity".into(), values);
+        let conditions = vec![condition];
+        let group = Group { conditions };
+
+        let mut node = new_node();
+        node.tags.insert("amenity".into(), "bar".into());
+        let obj = OsmObj::Node(node);
+        assert!(obj.filter(&[group.clone()]));
+
+        let mut node = new_node();
+        node.tags.insert("amenity".into(), "pub".into());
+        let obj = OsmObj::Node(node);
+        assert!(!obj.filter(&[group]));
+    }
+
+    #[test]
+    fn filter_regex_match() {
+        let condition = Condition::regex("name", "^Berlin").unwrap();
+        let conditions = vec![condition];
+        let group = Group { conditions };
+
+        let mut node = new_node();
+        node.tags
+            .insert("name".into(), "Berlin Hauptbahnhof".into());
+        let obj = OsmObj::Node(node);
+        assert!(obj.filter(&[group.clone()]));
+
+        let mut node = new_node();
+        node.tags.insert("name".into(), "Flughafen Berlin".into());
+        let obj = OsmObj::Node(node);
+        assert!(!obj.filter(&[group]));
+    }
+
+    #[test]
+    fn filter_negated_conditions() {
+        let condition_1 = Condition::TagPresence("building".into());
+        let condition_2 = Condition::TagPresence("addr:housenumber".into()).negate();
+        let conditions = vec![condition_1, condition_2];
+        let group = Group { conditions };
+
+        let mut node = new_node();
+        node.tags.insert("building".into(), "yes".into());
+        let obj = OsmObj::Node(node);
+        assert!(obj.filter(&[group.clone()]));
+
+        let mut node = new_node();
+        node.tags.insert("building".into(), "yes".into());
+        node.tags.insert("addr:housenumber".into(), "1".into());
+        let obj = OsmObj::Node(node);
+        assert!(!obj.filter(&[group]));
+
+        let condition = Condition::new("amenity", Some("parking")).negate();
+        let conditions = vec![condition];
+        let group = Group { conditions };
+
+        let mut node = new_node();
+        node.tags.insert("amenity".into(), "parking".into());
+        let obj = OsmObj::Node(node);
+        assert!(!obj.filter(&[group.clone()]));
+
+        let mut node = new_node();
+        node.tags.insert("amenity".into(), "cafe".into());
+        let obj = OsmObj::Node(node);
+        assert!(obj.filter(&[group]));
     }
-}
 
-#[derive(PartialEq, Debug, Clone)]
-pub struct Group {
-    pub conditions: Vec<Condition>,
-}
+    #[test]
+    fn filter_numeric_comparison() {
+        let condition = Condition::NumericComparison("maxspeed".into(), Comparison::Less, 60.);
+        let conditions = vec![condition];
+        let group = Group { conditions };
+
+        let mut node = new_node();
+        node.tags.insert("maxspeed".into(), "50 mph".into());
+        let obj = OsmObj::Node(node);
+        assert!(obj.filter(&[group.clone()]));
 
-fn parse_condition(condition_str: &str) -> Condition {
-    let split_str: Vec<&str> = condition_str.splitn(2, '~').collect();
-    if split_str.len() < 2 {
-        Condition::TagPresence(condition_str.into())
-    } else {
-        let key = split_str[0];
-        let value = split_str[1];
-        Condition::ValueMatch(key.into(), value.into())
+        let mut node = new_node();
+        node.tags.insert("maxspeed".into(), "60".into());
+        let obj = OsmObj::Node(node);
+        assert!(!obj.filter(&[group.clone()]));
+
+        let mut node = new_node();
+        node.tags.insert("maxspeed".into(), "walk".into());
+        let obj = OsmObj::Node(node);
+        assert!(!obj.filter(&[group]));
     }
-}
 
-fn parse_group(group_str: &str) -> Group {
-    let conditions = group_str.split('+').map(parse_condition).collect();
-    Group { conditions }
-}
+    #[test]
+    fn filter_element_type() {
+        let condition_1 = Condition::ElementType(ElementType::Way);
+        let condition_2 = Condition::TagPresence("amenity".into());
+        let conditions = vec![condition_1, condition_2];
+        let group = Group { conditions };
 
-/// Parse an expression into a filter groups
-///
-/// Stating a key (`amenity`), will pick all entities which are tagged using that key.
-/// To further narrow down the results, a specific value can be given using a `~` field
-/// separator (`amenity~fountain`). To check the presence of multiple tags for the same
-/// entity, statements can be combined using the `+` operator (`'amenity~fountain+tourism'`).
-/// Finally, options can be specified by concatenating groups of statements with `,`
-/// (`amenity~fountain+tourism,amenity~townhall`). If an entity matches the criteria of
-/// either group it will be included in the output.
-///
-/// # Example
-///
-/// ```
-/// use osm_pbf2json::filter::parse;
-///
-/// let groups = parse("amenity~fountain+tourism,amenity~townhall".into());
-/// assert_eq!(groups.len(), 2);
-/// let group = &groups[0];
-/// assert_eq!(group.conditions.len(), 2);
-/// ```
-pub fn parse(selector_str: &str) -> Vec<Group> {
-    selector_str.split(',').map(parse_group).collect()
-}
+        let mut node = new_node();
+        node.tags.insert("amenity".into(), "cafe".into());
+        let obj = OsmObj::Node(node);
+        assert!(!obj.filter(&[group.clone()]));
 
-fn check_condition(tags: &Tags, condition: &Condition) -> bool {
-    match condition {
-        Condition::TagPresence(key) => tags.contains_key(key.as_str()),
-        Condition::ValueMatch(key, value) => tags.contains(key, value),
+        let mut tags = Tags::new();
+        tags.insert("amenity".into(), "cafe".into());
+        let way = Way {
+            id: WayId(1),
+            tags,
+            nodes: vec![],
+        };
+        let obj = OsmObj::Way(way);
+        assert!(obj.filter(&[group]));
     }
-}
 
-fn check_group(tags: &Tags, group: &Group) -> bool {
-    group.conditions.iter().all(|c| check_condition(tags, c))
-}
+    #[test]
+    fn filter_membership() {
+        use osmpbfreader::objects::Ref;
 
-pub trait Filter {
-    fn filter(&self, groups: &[Group]) -> bool;
-}
+        let mut tags = Tags::new();
+        tags.insert("type".into(), "route".into());
+        tags.insert("route".into(), "bus".into());
+        let relation = Relation {
+            id: RelationId(1),
+            tags,
+            refs: vec![Ref {
+                member: NodeId(1).into(),
+                role: "stop".into(),
+            }],
+        };
+        let index = MemberIndex::new(vec![relation]);
 
-impl Filter for OsmObj {
-    fn filter(&self, groups: &[Group]) -> bool {
-        let tags = self.tags();
-        groups.iter().any(|c| check_group(tags, c))
+        let obj = OsmObj::Node(new_node());
+        let route = parse("member(type~route+route~bus)").unwrap();
+        let stop = parse("member[stop](type~route)").unwrap();
+        let platform = parse("member[platform](type~route)").unwrap();
+        let tram = parse("member(route~tram)").unwrap();
+        assert!(obj.filter_with_index(&route, &index));
+        assert!(obj.filter_with_index(&stop, &index));
+        assert!(!obj.filter_with_index(&platform, &index));
+        assert!(!obj.filter_with_index(&tram, &index));
+        assert!(!obj.filter(&route));
+
+        let way = Way {
+            id: WayId(1),
+            tags: Tags::new(),
+            nodes: vec![],
+        };
+        let obj = OsmObj::Way(way);
+        assert!(!obj.filter_with_index(&route, &index));
+        assert!(obj.filter_with_index(&parse("!member(type)").unwrap(), &index));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use osmpbfreader::objects::{Node, NodeId};
+    #[test]
+    fn filter_geometry() {
+        let obj = OsmObj::Node(new_node());
+        let index = MemberIndex::default();
+        let large = parse("@area>=100").unwrap();
+        let not_large = parse("!@area>=100").unwrap();
+        let short = parse("@length<5").unwrap();
 
-    fn new_node() -> Node {
-        let tags = Tags::new();
-        let id = NodeId(1);
-        Node {
-            id,
-            tags,
-            decimicro_lat: 0,
-            decimicro_lon: 0,
-        }
+        // without measures geometry conditions are assumed to hold
+        assert!(obj.filter(&large));
+        assert!(obj.filter(&not_large));
+
+        let measures = Measures {
+            area: Some(150.),
+            length: None,
+        };
+        assert!(obj.filter_with_measures(&large, &index, &measures));
+        assert!(!obj.filter_with_measures(&not_large, &index, &measures));
+        assert!(!obj.filter_with_measures(&short, &index, &measures));
+        assert!(obj.filter_with_measures(&parse("!@length<5").unwrap(), &index, &measures));
     }
 
     #[test]
-    fn filter_single_group() {
-        let condition = Condition::TagPresence("amenity".into());
-        let conditions = vec![condition];
-        let group = Group { conditions };
+    fn filter_ids() {
+        let ids = parse_ids("n1,w2").unwrap();
+        let group = Group {
+            conditions: vec![Condition::Ids(ids)],
+        };
 
-        let node = new_node();
-        let obj = OsmObj::Node(node);
+        let obj = OsmObj::Node(new_node());
+        assert!(obj.filter(&[group.clone()]));
 
-        assert!(!obj.filter(&[group.clone()]));
+        let way = Way {
+            id: WayId(1),
+            tags: Tags::new(),
+            nodes: vec![],
+        };
+        let obj = OsmObj::Way(way);
+        assert!(!obj.filter(&[group]));
+    }
+
+    #[test]
+    fn filter_wildcard_keys() {
+        let prefix_key = Key::Wildcard {
+            prefix: "addr:".into(),
+            suffix: "".into(),
+        };
+        let suffix_key = Key::Wildcard {
+            prefix: "".into(),
+            suffix: ":wikidata".into(),
+        };
+        let group_1 = Group {
+            conditions: vec![Condition::TagPresence(prefix_key.clone())],
+        };
+        let group_2 = Group {
+            conditions: vec![Condition::ValueMatch(prefix_key, "10178".into())],
+        };
+        let group_3 = Group {
+            conditions: vec![Condition::TagPresence(suffix_key)],
+        };
 
         let mut node = new_node();
-        node.tags.insert("amenity".into(), "theatre".into());
+        node.tags.insert("addr".into(), "yes".into());
+        node.tags.insert("address".into(), "Alexanderplatz".into());
+        node.tags.insert("wikidata".into(), "Q1".into());
         let obj = OsmObj::Node(node);
+        assert!(!obj.filter(&[group_1.clone()]));
+        assert!(!obj.filter(&[group_3.clone()]));
 
-        assert!(obj.filter(&[group]));
+        let mut node = new_node();
+        node.tags.insert("addr:city".into(), "Berlin".into());
+        node.tags.insert("addr:postcode".into(), "10178".into());
+        node.tags.insert("brand:wikidata".into(), "Q2".into());
+        let obj = OsmObj::Node(node);
+        assert!(obj.filter(&[group_1]));
+        assert!(obj.filter(&[group_2]));
+        assert!(obj.filter(&[group_3]));
     }
 
     #[test]
-    fn filter_value_match() {
-        let condition = Condition::ValueMatch("amenity".into(), "theatre".into());
-        let conditions = vec![condition];
-        let group = Group { conditions };
+    fn filter_normalized_values() {
+        let condition = Condition::new("name", Some("Straße")).normalized();
+        let group = Group {
+            conditions: vec![condition],
+        };
 
         let mut node = new_node();
-        node.tags.insert("amenity".into(), "theatre".into());
+        node.tags.insert("name".into(), "STRAße".into());
         let obj = OsmObj::Node(node);
         assert!(obj.filter(&[group.clone()]));
 
         let mut node = new_node();
-        node.tags.insert("amenity".into(), "cinema".into());
+        node.tags.insert("name".into(), "Strasse".into());
+        let obj = OsmObj::Node(node);
+        assert!(!obj.filter(&[group]));
+
+        let condition = Condition::regex("name", "^Bä").unwrap().normalized();
+        let group = Group {
+            conditions: vec![condition],
+        };
+
+        // decomposed "a" followed by a combining diaeresis
+        let mut node = new_node();
+        node.tags.insert("name".into(), "ba\u{308}ckerei".into());
+        let obj = OsmObj::Node(node);
+        assert!(obj.filter(&[group]));
+
+        // escapes with uppercase letters keep their meaning
+        let condition = Condition::regex("ref", r"^\S+\D$").unwrap().normalized();
+        let group = Group {
+            conditions: vec![condition],
+        };
+
+        let mut node = new_node();
+        node.tags.insert("ref".into(), "B2A".into());
+        let obj = OsmObj::Node(node);
+        assert!(obj.filter(&[group.clone()]));
+
+        let mut node = new_node();
+        node.tags.insert("ref".into(), "123".into());
         let obj = OsmObj::Node(node);
         assert!(!obj.filter(&[group]));
     }
@@ -178,7 +1487,7 @@ mod tests {
         let conditions = vec![condition];
         let group = Group { conditions };
 
-        assert_eq!(parse("amenity"), [group]);
+        assert_eq!(parse("amenity").unwrap(), [group]);
     }
 
     #[test]
@@ -192,7 +1501,7 @@ mod tests {
             conditions: vec![condition_2],
         };
 
-        assert_eq!(parse("amenity,highway"), [group_1, group_2]);
+        assert_eq!(parse("amenity,highway").unwrap(), [group_1, group_2]);
     }
 
     #[test]
@@ -202,7 +1511,7 @@ mod tests {
         let conditions = vec![condition_1, condition_2];
         let group = Group { conditions };
 
-        assert_eq!(parse("amenity+highway"), vec![group]);
+        assert_eq!(parse("amenity+highway").unwrap(), vec![group]);
     }
 
     #[test]
@@ -211,6 +1520,420 @@ mod tests {
         let conditions = vec![condition];
         let group = Group { conditions };
 
-        assert_eq!(parse("amenity~theatre"), vec![group]);
+        assert_eq!(parse("amenity~theatre").unwrap(), vec![group]);
+    }
+
+    #[test]
+    fn parse_value_alternatives() {
+        let values = vec!["cafe".into(), "restaurant".into(), "bar".into()];
+        let condition_1 = Condition::ValueAlternatives("amenity".into(), values);
+        let condition_2 = Condition::TagPresence("name".into());
+        let conditions = vec![condition_1, condition_2];
+        let group = Group { conditions };
+
+        assert_eq!(
+            parse("amenity~cafe|restaurant|bar+name").unwrap(),
+            vec![group]
+        );
+    }
+
+    #[test]
+    fn parse_element_types() {
+        let group_1 = Group {
+            conditions: vec![
+                Condition::ElementType(ElementType::Node),
+                Condition::TagPresence("amenity".into()),
+            ],
+        };
+        let group_2 = Group {
+            conditions: vec![
+                Condition::ElementType(ElementType::Relation),
+                Condition::ValueMatch("type".into(), "route".into()),
+            ],
+        };
+        let group_3 = Group {
+            conditions: vec![Condition::TagPresence("addr:street".into())],
+        };
+
+        assert_eq!(
+            parse("node:amenity,rel:type~route,addr:street").unwrap(),
+            vec![group_1, group_2, group_3]
+        );
+    }
+
+    #[test]
+    fn parse_wildcard_keys() {
+        let key_1 = Key::Wildcard {
+            prefix: "addr:".into(),
+            suffix: "".into(),
+        };
+        let key_2 = Key::Wildcard {
+            prefix: "name:".into(),
+            suffix: "".into(),
+        };
+        let key_3 = Key::Wildcard {
+            prefix: "".into(),
+            suffix: ":wikidata".into(),
+        };
+        let conditions = vec![
+            Condition::TagPresence(key_1).negate(),
+            Condition::ValueMatch(key_2, "Berlin".into()),
+            Condition::TagPresence(key_3),
+            Condition::TagPresence("fixme*".into()),
+        ];
+        let group = Group { conditions };
+
+        assert_eq!(
+            parse(r#"!addr:*+name:*~Berlin+*:wikidata+fixme\*"#).unwrap(),
+            vec![group]
+        );
+        assert_eq!(
+            parse("addr:*:*").unwrap_err(),
+            ParseError {
+                position: 7,
+                kind: ParseErrorKind::UnexpectedCharacter('*'),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_normalized_conditions() {
+        let values = vec!["yes".into(), "limited".into()];
+        let condition_1 = Condition::ValueAlternatives("wheelchair".into(), values);
+        let condition_2 = Condition::ValueMatch("name".into(), "straße".into());
+        let conditions = vec![
+            Condition::Normalized(Box::new(condition_1)),
+            Condition::Normalized(Box::new(condition_2)).negate(),
+        ];
+        let group = Group { conditions };
+
+        assert_eq!(
+            parse("wheelchair~*Yes|LIMITED+name!~*Straße").unwrap(),
+            vec![group]
+        );
+    }
+
+    #[test]
+    fn parse_regex_match() {
+        let condition = Condition::regex("addr:postcode", "^101..$").unwrap();
+        let conditions = vec![condition];
+        let group = Group { conditions };
+
+        assert_eq!(parse("addr:postcode~/^101..$/").unwrap(), vec![group]);
+    }
+
+    #[test]
+    fn parse_negated_conditions() {
+        let condition_1 = Condition::TagPresence("amenity".into());
+        let condition_2 = Condition::new("amenity", Some("parking")).negate();
+        let condition_3 = Condition::TagPresence("name".into()).negate();
+        let conditions = vec![condition_1, condition_2, condition_3];
+        let group = Group { conditions };
+
+        assert_eq!(
+            parse("amenity+amenity!~parking+!name").unwrap(),
+            vec![group]
+        );
+    }
+
+    #[test]
+    fn parse_numeric_comparisons() {
+        let condition_1 = Condition::NumericComparison("capacity".into(), Comparison::Greater, 5.);
+        let condition_2 =
+            Condition::NumericComparison("capacity".into(), Comparison::LessOrEqual, 10.);
+        let condition_3 =
+            Condition::NumericComparison("admin_level".into(), Comparison::GreaterOrEqual, 8.);
+        let condition_4 =
+            Condition::NumericComparison("building:levels".into(), Comparison::Less, 2.5);
+        let group_1 = Group {
+            conditions: vec![condition_1, condition_2],
+        };
+        let group_2 = Group {
+            conditions: vec![condition_3, condition_4],
+        };
+
+        assert_eq!(
+            parse("capacity>5+capacity<=10,admin_level>=8+building:levels<2.5").unwrap(),
+            vec![group_1, group_2]
+        );
+
+        let condition = Condition::NumericComparison("capacity".into(), Comparison::Greater, 1e3);
+        assert_eq!(
+            parse("capacity>1e3").unwrap()[0].conditions,
+            vec![condition]
+        );
+    }
+
+    #[test]
+    fn parse_nested_groups() {
+        let group_1 = Group {
+            conditions: vec![Condition::TagPresence("amenity".into())],
+        };
+        let group_2 = Group {
+            conditions: vec![Condition::TagPresence("shop".into())],
+        };
+        let condition_1 = Condition::Any(vec![group_1, group_2]);
+        let condition_2 = Condition::TagPresence("name".into());
+        let condition_3 = Condition::TagPresence("wheelchair".into());
+        let conditions = vec![condition_1, condition_2, condition_3];
+        let group = Group { conditions };
+
+        assert_eq!(
+            parse("(amenity,shop)+(name+wheelchair)").unwrap(),
+            vec![group]
+        );
+    }
+
+    #[test]
+    fn parse_negated_nested_group() {
+        let group_1 = Group {
+            conditions: vec![Condition::TagPresence("addr:street".into())],
+        };
+        let group_2 = Group {
+            conditions: vec![Condition::TagPresence("addr:place".into())],
+        };
+        let condition_1 = Condition::TagPresence("building".into());
+        let condition_2 = Condition::Any(vec![group_1, group_2]).negate();
+        let conditions = vec![condition_1, condition_2];
+        let group = Group { conditions };
+
+        assert_eq!(
+            parse("building+!(addr:street,addr:place)").unwrap(),
+            vec![group]
+        );
+    }
+
+    #[test]
+    fn parse_quoted_and_escaped_values() {
+        let condition = Condition::ValueMatch("name".into(), "Café, Bar (Mitte)".into());
+        let group = Group {
+            conditions: vec![condition],
+        };
+
+        assert_eq!(
+            parse(r#"name~"Café, Bar (Mitte)""#).unwrap(),
+            vec![group.clone()]
+        );
+        assert_eq!(parse(r#"name~Café\, Bar \(Mitte\)"#).unwrap(), vec![group]);
+
+        let condition = Condition::ValueMatch("note".into(), r#"say "hi""#.into());
+        let group = Group {
+            conditions: vec![condition],
+        };
+
+        assert_eq!(parse(r#"note~"say \"hi\"""#).unwrap(), vec![group]);
+    }
+
+    #[test]
+    fn parse_unquoted_values() {
+        let expected = vec![
+            Group {
+                conditions: vec![
+                    Condition::new("name", Some("Café (Mitte)")),
+                    Condition::new("amenity", Some("")),
+                ],
+            },
+            Group {
+                conditions: vec![Condition::new("note", Some("a~b"))],
+            },
+        ];
+        assert_eq!(
+            parse("name~Café (Mitte)+amenity~,note~a~b").unwrap(),
+            expected
+        );
+
+        let values = vec![r#"The "Bar""#.into(), "a)b".into()];
+        let expected = vec![
+            Group {
+                conditions: vec![Condition::ValueAlternatives("name".into(), values)],
+            },
+            Group {
+                conditions: vec![
+                    Condition::new("name", Some("Café (Mitte)")),
+                    Condition::new("shop", None),
+                ],
+            },
+        ];
+        assert_eq!(
+            parse(r#"name~The "Bar"|a)b,(name~Café (Mitte))+shop"#).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn parse_regex_with_delimiters() {
+        let condition_1 = Condition::regex("name", "^(Alt|Neu)-.+/.+$").unwrap();
+        let condition_2 = Condition::TagPresence("highway".into());
+        let conditions = vec![condition_1, condition_2];
+        let group = Group { conditions };
+
+        assert_eq!(
+            parse(r#"name~/^(Alt|Neu)-.+\/.+$/+highway"#).unwrap(),
+            vec![group]
+        );
+    }
+
+    #[test]
+    fn parse_membership() {
+        let groups = parse("node:member[stop](type~route,type~stop_area)").unwrap();
+        let inner = vec![
+            Group {
+                conditions: vec![Condition::new("type", Some("route"))],
+            },
+            Group {
+                conditions: vec![Condition::new("type", Some("stop_area"))],
+            },
+        ];
+        let expected = Group {
+            conditions: vec![
+                Condition::ElementType(ElementType::Node),
+                Condition::MemberOf(Some("stop".into()), inner),
+            ],
+        };
+        assert_eq!(groups, vec![expected]);
+
+        let groups = parse("!member(type~route)+member~yes").unwrap();
+        let inner = parse("type~route").unwrap();
+        let expected = Group {
+            conditions: vec![
+                Condition::MemberOf(None, inner).negate(),
+                Condition::new("member", Some("yes")),
+            ],
+        };
+        assert_eq!(groups, vec![expected]);
+
+        let error = |kind, position| Err(ParseError { kind, position });
+        assert_eq!(
+            parse("member[stop(type~route)"),
+            error(ParseErrorKind::UnexpectedEnd, 23)
+        );
+        assert_eq!(
+            parse("member[stop]type"),
+            error(ParseErrorKind::UnexpectedCharacter('t'), 12)
+        );
+    }
+
+    #[test]
+    fn parse_measures() {
+        let groups = parse("building+@area>=50.5,!@length<5").unwrap();
+        let expected = vec![
+            Group {
+                conditions: vec![
+                    Condition::new("building", None),
+                    Condition::Geometry(Measure::Area, Comparison::GreaterOrEqual, 50.5),
+                ],
+            },
+            Group {
+                conditions: vec![
+                    Condition::Geometry(Measure::Length, Comparison::Less, 5.).negate()
+                ],
+            },
+        ];
+        assert_eq!(groups, expected);
+        assert!(groups[0].uses_geometry() && groups[1].uses_geometry());
+        assert!(!parse(r#""@area"~x"#).unwrap()[0].uses_geometry());
+
+        let error = |kind, position| Err(ParseError { kind, position });
+        assert_eq!(
+            parse("@volume>5"),
+            error(ParseErrorKind::UnknownMeasure("volume".into()), 0)
+        );
+        assert_eq!(
+            parse("@area~5"),
+            error(ParseErrorKind::UnexpectedCharacter('~'), 5)
+        );
+        assert_eq!(
+            parse("@area>=inf"),
+            error(ParseErrorKind::InvalidNumber("inf".into()), 7)
+        );
+    }
+
+    #[test]
+    fn parse_labels() {
+        let groups = parse_labeled("[food]amenity~cafe,shop,[\"a, b\"]node:(tourism)").unwrap();
+        let labels: Vec<_> = groups.iter().map(|(label, _)| label.as_deref()).collect();
+        assert_eq!(labels, vec![Some("food"), None, Some("a, b")]);
+        assert_eq!(groups[2].1.conditions.len(), 2);
+        assert_eq!(parse("[food]amenity").unwrap(), parse("amenity").unwrap());
+
+        let error = |kind, position| Err(ParseError { kind, position });
+        assert_eq!(parse("[]amenity"), error(ParseErrorKind::ExpectedValue, 1));
+        assert_eq!(parse("[food"), error(ParseErrorKind::UnexpectedEnd, 5));
+    }
+
+    #[test]
+    fn parse_id_lists() {
+        let ids = parse_ids("n123,w456 r789\nn123,").unwrap();
+        let expected: BTreeSet<OsmId> = vec![
+            OsmId::Node(NodeId(123)),
+            OsmId::Way(WayId(456)),
+            OsmId::Relation(RelationId(789)),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(ids, expected);
+        assert!(parse_ids("").unwrap().is_empty());
+
+        let ids = parse_ids("n-5,w-1").unwrap();
+        let expected: BTreeSet<OsmId> = vec![OsmId::Node(NodeId(-5)), OsmId::Way(WayId(-1))]
+            .into_iter()
+            .collect();
+        assert_eq!(ids, expected);
+
+        let error = |value: &str, position| ParseError {
+            kind: ParseErrorKind::InvalidId(value.into()),
+            position,
+        };
+        assert_eq!(parse_ids("n1, x2"), Err(error("x2", 4)));
+        assert_eq!(parse_ids("n1,w"), Err(error("w", 3)));
+        assert_eq!(parse_ids("n1,r2a"), Err(error("r2a", 3)));
+        assert_eq!(parse_ids("n1,n-"), Err(error("n-", 3)));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |kind, position| Err(ParseError { kind, position });
+
+        assert_eq!(parse("capacity>"), error(ParseErrorKind::ExpectedValue, 9));
+        assert_eq!(
+            parse("amenity,,shop"),
+            error(ParseErrorKind::ExpectedKey, 8)
+        );
+        assert_eq!(parse("~cafe"), error(ParseErrorKind::ExpectedKey, 0));
+        assert_eq!(
+            parse("(amenity,shop"),
+            error(ParseErrorKind::UnexpectedEnd, 13)
+        );
+        assert_eq!(
+            parse("amenity)"),
+            error(ParseErrorKind::UnexpectedCharacter(')'), 7)
+        );
+        assert_eq!(
+            parse("(name~Café (Mitte)"),
+            error(ParseErrorKind::UnexpectedEnd, 18)
+        );
+        assert_eq!(
+            parse(r#"name~"Café"#),
+            error(ParseErrorKind::UnterminatedString, 5)
+        );
+        assert_eq!(
+            parse("name~/^Berlin"),
+            error(ParseErrorKind::UnterminatedPattern, 5)
+        );
+        assert_eq!(
+            parse("capacity>many"),
+            error(ParseErrorKind::InvalidNumber("many".into()), 9)
+        );
+        assert_eq!(
+            parse("capacity>5x"),
+            error(ParseErrorKind::InvalidNumber("5x".into()), 9)
+        );
+    }
+
+    #[test]
+    fn parse_invalid_regex() {
+        let error = parse("name~/(/").unwrap_err();
+        assert_eq!(error.position, 5);
+        assert!(matches!(error.kind, ParseErrorKind::InvalidPattern(_)));
     }
 }
@@ -0,0 +1,402 @@
+use super::{
+    check_condition, parse_number, Comparison, Condition, Context, ElementType, Group, Key,
+    Measures, MemberIndex, Pattern,
+};
+use osmpbfreader::objects::OsmObj;
+use smartstring::alias::String;
+use std::collections::HashMap;
+
+type KeyId = usize;
+type ValueId = usize;
+
+#[derive(Debug)]
+enum Test {
+    Present(KeyId),
+    Value(KeyId, ValueId),
+    Values(KeyId, Vec<ValueId>),
+    Regex(KeyId, Pattern),
+    Numeric(KeyId, Comparison, f64),
+    ElementType(ElementType),
+    Any(Vec<CompiledGroup>),
+    Not(Box<Test>),
+    Fallback(Condition),
+}
+
+#[derive(Debug)]
+struct CompiledGroup {
+    required: Vec<KeyId>,
+    tests: Vec<Test>,
+    /// Conditions on the geometry, which only hold for certain once its measures are known
+    deferred: Vec<Condition>,
+}
+
+/// Value of a tag with an interned key, with the id of the value if it is interned as well
+#[derive(Clone, Copy)]
+struct Slot<'a> {
+    value: &'a String,
+    id: Option<ValueId>,
+}
+
+/// Filter groups compiled for evaluation against a large number of objects
+///
+/// Exact keys and the values they are compared to are interned on compilation. An object's
+/// tags are matched against the interned keys in a single pass over both sorted lists, which
+/// also looks up the ids of their values. Afterwards conditions find values by key id and
+/// compare value ids. Groups requiring a key the object lacks are skipped right away.
+/// Conditions which do not benefit from interning are evaluated like in `Filter`.
+///
+/// Geometry conditions are assumed to hold, as in `Filter::filter_with_index`, and can be
+/// checked for the groups which matched with `matches_with_measures`.
+#[derive(Debug)]
+pub struct CompiledFilter {
+    keys: Vec<(String, KeyId)>,
+    values: Vec<HashMap<String, ValueId>>,
+    groups: Vec<CompiledGroup>,
+    requires_tags: bool,
+}
+
+impl CompiledFilter {
+    pub fn new(groups: &[Group]) -> Self {
+        let mut compiler = Compiler::default();
+        let groups: Vec<CompiledGroup> = groups.iter().map(|g| compiler.compile_group(g)).collect();
+        let mut keys: Vec<(String, KeyId)> = compiler.keys.into_iter().collect();
+        keys.sort_unstable();
+        let requires_tags = groups.iter().all(|group| !group.required.is_empty());
+        CompiledFilter {
+            keys,
+            values: compiler.values,
+            groups,
+            requires_tags,
+        }
+    }
+
+    /// Equivalent to `Filter::filter_with_index` for the groups the filter was compiled from
+    pub fn matches(&self, obj: &OsmObj, index: &MemberIndex) -> bool {
+        if self.requires_tags && obj.tags().is_empty() {
+            return false;
+        }
+        let slots = self.slots(obj);
+        let ctx = Context {
+            index,
+            measures: None,
+        };
+        self.groups
+            .iter()
+            .any(|group| self.check_group(group, obj, &slots, &ctx))
+    }
+
+    /// Indices of all groups matching the object
+    pub fn matching_groups(&self, obj: &OsmObj, index: &MemberIndex) -> Vec<usize> {
+        if self.requires_tags && obj.tags().is_empty() {
+            return vec![];
+        }
+        let slots = self.slots(obj);
+        let ctx = Context {
+            index,
+            measures: None,
+        };
+        self.groups
+            .iter()
+            .enumerate()
+            .filter(|(_, group)| self.check_group(group, obj, &slots, &ctx))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Whether any group has geometry conditions, which need to be checked once the measures
+    /// of an object's geometry are known
+    pub fn uses_geometry(&self) -> bool {
+        self.groups.iter().any(|group| !group.deferred.is_empty())
+    }
+
+    /// Whether a group which matched the object still matches given the measures of its
+    /// geometry, only the group's geometry conditions are evaluated
+    pub fn matches_with_measures(
+        &self,
+        group: usize,
+        obj: &OsmObj,
+        index: &MemberIndex,
+        measures: &Measures,
+    ) -> bool {
+        let ctx = Context {
+            index,
+            measures: Some(measures),
+        };
+        self.groups[group]
+            .deferred
+            .iter()
+            .all(|condition| check_condition(obj, condition, &ctx))
+    }
+
+    /// Values of the object's tags, indexed by the ids of their keys
+    fn slots<'a>(&self, obj: &'a OsmObj) -> Vec<Option<Slot<'a>>> {
+        let mut slots = vec![None; self.values.len()];
+        let mut keys = self.keys.iter().peekable();
+        for (key, value) in obj.tags().iter() {
+            while let Some((interned, _)) = keys.peek() {
+                if interned.as_str() >= key.as_str() {
+                    break;
+                }
+                keys.next();
+            }
+            match keys.peek() {
+                None => break,
+                Some((interned, id)) if interned == key => {
+                    let value_id = self.values[*id].get(value.as_str()).copied();
+                    slots[*id] = Some(Slot {
+                        value,
+                        id: value_id,
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+        slots
+    }
+
+    fn check_group(
+        &self,
+        group: &CompiledGroup,
+        obj: &OsmObj,
+        slots: &[Option<Slot>],
+        ctx: &Context,
+    ) -> bool {
+        group.required.iter().all(|id| slots[*id].is_some())
+            && group
+                .tests
+                .iter()
+                .all(|test| self.check(test, obj, slots, ctx))
+    }
+
+    fn check(&self, test: &Test, obj: &OsmObj, slots: &[Option<Slot>], ctx: &Context) -> bool {
+        match test {
+            Test::Present(key) => slots[*key].is_some(),
+            Test::Value(key, expected) => {
+                slots[*key].is_some_and(|slot| slot.id == Some(*expected))
+            }
+            Test::Values(key, expected) => slots[*key]
+                .and_then(|slot| slot.id)
+                .is_some_and(|value| expected.contains(&value)),
+            Test::Regex(key, pattern) => {
+                slots[*key].is_some_and(|slot| pattern.is_match(slot.value))
+            }
+            Test::Numeric(key, comparison, number) => slots[*key]
+                .and_then(|slot| parse_number(slot.value))
+                .is_some_and(|value| comparison.holds(value, *number)),
+            Test::ElementType(element_type) => element_type.matches(obj),
+            Test::Any(groups) => groups
+                .iter()
+                .any(|group| self.check_group(group, obj, slots, ctx)),
+            Test::Not(test) => !self.check(test, obj, slots, ctx),
+            Test::Fallback(condition) => check_condition(obj, condition, ctx),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Compiler {
+    keys: HashMap<String, KeyId>,
+    values: Vec<HashMap<String, ValueId>>,
+}
+
+impl Compiler {
+    fn key(&mut self, key: &str) -> KeyId {
+        if let Some(id) = self.keys.get(key) {
+            return *id;
+        }
+        let id = self.values.len();
+        self.keys.insert(key.into(), id);
+        self.values.push(HashMap::new());
+        id
+    }
+
+    fn value(&mut self, key: KeyId, value: &str) -> ValueId {
+        let values = &mut self.values[key];
+        if let Some(id) = values.get(value) {
+            return *id;
+        }
+        let id = values.len();
+        values.insert(value.into(), id);
+        id
+    }
+
+    fn compile_group(&mut self, group: &Group) -> CompiledGroup {
+        let tests: Vec<Test> = group
+            .conditions
+            .iter()
+            .map(|condition| self.compile(condition))
+            .collect();
+        let required = tests
+            .iter()
+            .filter_map(|test| match test {
+                Test::Present(key)
+                | Test::Value(key, _)
+                | Test::Values(key, _)
+                | Test::Regex(key, _)
+                | Test::Numeric(key, _, _) => Some(*key),
+                _ => None,
+            })
+            .collect();
+        let deferred = group
+            .conditions
+            .iter()
+            .filter(|condition| condition.uses_geometry())
+            .cloned()
+            .collect();
+        CompiledGroup {
+            required,
+            tests,
+            deferred,
+        }
+    }
+
+    fn compile(&mut self, condition: &Condition) -> Test {
+        if condition.uses_geometry() || condition.uses_membership() {
+            return Test::Fallback(condition.clone());
+        }
+        match condition {
+            Condition::TagPresence(Key::Exact(key)) => Test::Present(self.key(key)),
+            Condition::ValueMatch(Key::Exact(key), value) => {
+                let key = self.key(key);
+                Test::Value(key, self.value(key, value))
+            }
+            Condition::ValueAlternatives(Key::Exact(key), values) => {
+                let key = self.key(key);
+                let values = values.iter().map(|value| self.value(key, value)).collect();
+                Test::Values(key, values)
+            }
+            Condition::RegexMatch(Key::Exact(key), pattern) => {
+                Test::Regex(self.key(key), pattern.clone())
+            }
+            Condition::NumericComparison(Key::Exact(key), comparison, number) => {
+                Test::Numeric(self.key(key), *comparison, *number)
+            }
+            Condition::ElementType(element_type) => Test::ElementType(*element_type),
+            Condition::Any(groups) => {
+                Test::Any(groups.iter().map(|g| self.compile_group(g)).collect())
+            }
+            Condition::Not(condition) => Test::Not(Box::new(self.compile(condition))),
+            condition => Test::Fallback(condition.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{parse, Filter};
+    use super::*;
+    use osmpbfreader::objects::{Node, NodeId, Tags, Way, WayId};
+
+    fn objects() -> Vec<OsmObj> {
+        let tag_lists = vec![
+            vec![],
+            vec![("amenity", "cafe"), ("wheelchair", "yes")],
+            vec![
+                ("amenity", "bar"),
+                ("capacity", "25 seats"),
+                ("name", "Bar"),
+            ],
+            vec![("addr:street", "Torstraße"), ("building", "yes")],
+            vec![
+                ("highway", "residential"),
+                ("maxspeed", "30"),
+                ("name", "Tor"),
+            ],
+        ];
+        tag_lists
+            .into_iter()
+            .enumerate()
+            .flat_map(|(i, tag_list)| {
+                let mut tags = Tags::new();
+                for (key, value) in tag_list {
+                    tags.insert(key.into(), value.into());
+                }
+                let node = Node {
+                    id: NodeId(i as i64),
+                    tags: tags.clone(),
+                    decimicro_lat: 0,
+                    decimicro_lon: 0,
+                };
+                let way = Way {
+                    id: WayId(i as i64),
+                    tags,
+                    nodes: vec![],
+                };
+                vec![node.into(), way.into()]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_like_filter() {
+        let expressions = [
+            "amenity",
+            "amenity~cafe",
+            "amenity~cafe|bar+!wheelchair~yes",
+            "name~/^T/",
+            "capacity>=20,maxspeed<50",
+            "node:amenity,way:highway",
+            "(amenity,building)+!(name,wheelchair)",
+            "addr:*~Torstraße",
+            "name~*bar",
+            "highway+!maxspeed!~30",
+            "!amenity+!highway",
+        ];
+        let index = MemberIndex::default();
+        for expression in expressions.iter() {
+            let groups = parse(expression).unwrap();
+            let compiled = CompiledFilter::new(&groups);
+            for obj in objects() {
+                assert_eq!(
+                    compiled.matches(&obj, &index),
+                    obj.filter(&groups),
+                    "{} on {:?}",
+                    expression,
+                    obj.id()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn report_matching_groups() {
+        let groups = parse("amenity,amenity~bar,name,building").unwrap();
+        let compiled = CompiledFilter::new(&groups);
+        let index = MemberIndex::default();
+        let objs = objects();
+        assert!(compiled.matching_groups(&objs[0], &index).is_empty());
+        assert_eq!(compiled.matching_groups(&objs[4], &index), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn check_deferred_geometry_conditions() {
+        let groups = parse("amenity+@area>=100,amenity~cafe,!@length<5").unwrap();
+        let compiled = CompiledFilter::new(&groups);
+        assert!(compiled.uses_geometry());
+        assert!(!CompiledFilter::new(&parse("amenity").unwrap()).uses_geometry());
+
+        let index = MemberIndex::default();
+        let obj = &objects()[2];
+        assert_eq!(compiled.matching_groups(obj, &index), vec![0, 1, 2]);
+        let measures = Measures {
+            area: Some(50.),
+            length: None,
+        };
+        let matching: Vec<usize> = (0..3)
+            .filter(|&i| compiled.matches_with_measures(i, obj, &index, &measures))
+            .collect();
+        assert_eq!(matching, vec![1, 2]);
+    }
+
+    #[test]
+    fn intern_keys_and_values() {
+        let groups = parse("amenity~cafe,amenity~cafe|bar+name,addr:*").unwrap();
+        let compiled = CompiledFilter::new(&groups);
+        let keys: Vec<&str> = compiled.keys.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, vec!["amenity", "name"]);
+        assert_eq!(compiled.values.iter().map(HashMap::len).sum::<usize>(), 2);
+        assert_eq!(compiled.values[0].get("bar"), Some(&1));
+        assert_eq!(compiled.groups[1].required.len(), 2);
+        assert!(compiled.groups[2].required.is_empty());
+    }
+}
@@ -0,0 +1,216 @@
+use super::{parse_labeled, Condition, Group, LabeledGroup};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+
+/// A named set of filter groups, given as an expression, as explicit groups, or both
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Profile {
+    #[serde(default)]
+    label: Option<String>,
+    #[serde(default)]
+    expression: Option<String>,
+    #[serde(default)]
+    groups: Vec<ProfileGroup>,
+}
+
+/// An explicit filter group of a profile, optionally labeled
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfileGroup {
+    #[serde(default)]
+    label: Option<String>,
+    conditions: Vec<Condition>,
+}
+
+impl Profile {
+    /// Groups parsed from the expression followed by the explicit ones, the profile's label
+    /// applies to all groups without their own
+    fn groups(&self) -> Result<Vec<LabeledGroup>, Box<dyn Error>> {
+        let mut groups = match &self.expression {
+            Some(expression) => parse_labeled(expression)?,
+            None => vec![],
+        };
+        groups.extend(self.groups.iter().map(|group| {
+            let conditions = group.conditions.clone();
+            (group.label.clone(), Group { conditions })
+        }));
+        if let Some(label) = &self.label {
+            for (group_label, _) in groups.iter_mut().filter(|(label, _)| label.is_none()) {
+                *group_label = Some(label.clone());
+            }
+        }
+        Ok(groups)
+    }
+}
+
+/// Filter profiles by name, as loaded from a TOML or JSON file
+///
+/// Each top-level entry is a profile, e.g. in TOML:
+///
+/// ```toml
+/// [pois]
+/// label = "poi"
+/// expression = "amenity~cafe|restaurant,tourism"
+///
+/// [addresses]
+/// groups = [{ conditions = [{ tag_presence = "addr:housenumber" }] }]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(transparent)]
+pub struct Profiles(BTreeMap<String, Profile>);
+
+impl Profiles {
+    /// TOML is read into a JSON value first, as the `toml` crate does not support
+    /// conditions with multiple operands, i.e. enum variants given as arrays
+    pub fn from_toml(toml_str: &str) -> Result<Self, Box<dyn Error>> {
+        let value: serde_json::Value = toml::from_str(toml_str)?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    pub fn from_json(json_str: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json_str)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str)
+    }
+
+    /// Groups of the named profiles in the given order, or of all profiles if no names are given
+    pub fn groups(&self, names: &[&str]) -> Result<Vec<LabeledGroup>, Box<dyn Error>> {
+        let names: Vec<&str> = match names {
+            [] => self.names().collect(),
+            names => names.to_vec(),
+        };
+        let mut groups = vec![];
+        for name in names {
+            let profile = self
+                .0
+                .get(name)
+                .ok_or_else(|| format!("filter profile '{}' not found", name))?;
+            let profile_groups = profile
+                .groups()
+                .map_err(|e| format!("filter profile '{}': {}", name, e))?;
+            groups.extend(profile_groups);
+        }
+        Ok(groups)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::test_helpers::create_tags;
+    use super::super::{parse, Comparison, ElementType, Filter, Key, Pattern};
+    use super::*;
+    use osmpbfreader::objects::{Node, NodeId, OsmId, OsmObj, WayId};
+
+    const PROFILES: &str = r#"
+[pois]
+label = "poi"
+expression = "amenity~cafe|restaurant,[sights]tourism"
+
+[[addresses.groups]]
+label = "address"
+conditions = [
+    { tag_presence = "addr:housenumber" },
+    { not = { regex_match = ["addr:*", "^$"] } },
+]
+
+[[misc.groups]]
+conditions = [
+    { numeric_comparison = ["maxspeed", "<", 50] },
+    { element_type = "way" },
+    { ids = "n1,w2" },
+]
+"#;
+
+    #[test]
+    fn load_toml_profiles() {
+        let profiles = Profiles::from_toml(PROFILES).unwrap();
+        let names: Vec<&str> = profiles.names().collect();
+        assert_eq!(names, vec!["addresses", "misc", "pois"]);
+
+        let groups = profiles.groups(&["pois", "addresses"]).unwrap();
+        let labels: Vec<_> = groups.iter().map(|(label, _)| label.as_deref()).collect();
+        assert_eq!(labels, vec![Some("poi"), Some("sights"), Some("address")]);
+        let condition = &groups[2].1.conditions[1];
+        let key = Key::Wildcard {
+            prefix: "addr:".into(),
+            suffix: "".into(),
+        };
+        let pattern = Pattern::new("^$").unwrap();
+        assert_eq!(condition, &Condition::RegexMatch(key, pattern).negate());
+
+        let groups = profiles.groups(&["misc"]).unwrap();
+        let ids = vec![OsmId::Node(NodeId(1)), OsmId::Way(WayId(2))];
+        assert_eq!(
+            groups[0].1.conditions,
+            vec![
+                Condition::NumericComparison("maxspeed".into(), Comparison::Less, 50.0),
+                Condition::ElementType(ElementType::Way),
+                Condition::Ids(ids.into_iter().collect()),
+            ]
+        );
+
+        assert_eq!(profiles.groups(&[]).unwrap().len(), 4);
+        assert!(profiles.groups(&["transport"]).is_err());
+    }
+
+    #[test]
+    fn load_json_profiles() {
+        let json = r#"{
+            "transport": {
+                "expression": "public_transport~stop_position",
+                "groups": [{ "conditions": [{ "member_of": ["stop", [
+                    { "conditions": [{ "value_match": ["type", "route"] }] }
+                ]] }] }]
+            }
+        }"#;
+        let profiles = Profiles::from_json(json).unwrap();
+        let groups = profiles.groups(&["transport"]).unwrap();
+        assert_eq!(groups.len(), 2);
+        assert!(groups[1].1.uses_membership());
+    }
+
+    #[test]
+    fn normalize_profile_values() {
+        let toml_str = r#"
+[accessible]
+groups = [{ conditions = [
+    { normalized = { value_match = ["wheelchair", "Yes"] } },
+    { normalized = { regex_match = ["name", "^CAFÉ"] } },
+] }]
+"#;
+        let groups = Profiles::from_toml(toml_str).unwrap().groups(&[]).unwrap();
+        let groups: Vec<Group> = groups.into_iter().map(|(_, group)| group).collect();
+        assert_eq!(groups, parse("wheelchair~*Yes+name~*/^CAFÉ/").unwrap());
+
+        let node = Node {
+            id: NodeId(1),
+            tags: create_tags(&[("wheelchair", "YES"), ("name", "Café Moskau")]),
+            decimicro_lat: 0,
+            decimicro_lon: 0,
+        };
+        assert!(OsmObj::Node(node).filter(&groups));
+    }
+
+    #[test]
+    fn reject_invalid_profiles() {
+        let invalid = [
+            "[pois]\nexpression = \"amenity+\"",
+            "[pois]\ngroups = [{ conditions = [{ regex_match = [\"name\", \"(\"] }] }]",
+            "[pois]\ngroups = [{ conditions = [{ tag_presence = \"a*b*\" }] }]",
+            "[pois]\ngroups = [{ conditions = [{ ids = \"x1\" }] }]",
+            "[pois]\nexpresion = \"amenity\"",
+        ];
+        for toml_str in invalid.iter() {
+            let loaded = Profiles::from_toml(toml_str);
+            assert!(
+                loaded.is_err() || loaded.unwrap().groups(&[]).is_err(),
+                "{}",
+                toml_str
+            );
+        }
+    }
+}
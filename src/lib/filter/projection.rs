@@ -0,0 +1,141 @@
+use super::Key;
+use osmpbfreader::objects::Tags;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::error::Error;
+
+/// Selection and renaming of the tags written for an object
+///
+/// Tags are kept if they match one of the `keep` keys (or if none are given) and none of the
+/// `drop` keys. Renaming is applied afterwards, so keys are always given by their original name.
+/// A renamed tag replaces a retained tag which already has the new key.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TagProjection {
+    pub keep: Vec<Key>,
+    pub drop: Vec<Key>,
+    pub rename: BTreeMap<String, String>,
+}
+
+impl TagProjection {
+    pub fn is_identity(&self) -> bool {
+        self.keep.is_empty() && self.drop.is_empty() && self.rename.is_empty()
+    }
+
+    fn retains(&self, key: &str) -> bool {
+        (self.keep.is_empty() || self.keep.iter().any(|k| k.matches(key)))
+            && !self.drop.iter().any(|k| k.matches(key))
+    }
+
+    pub fn apply(&self, tags: &Tags) -> Tags {
+        let mut projected = Tags::new();
+        let mut renamed = vec![];
+        for (key, value) in tags.iter().filter(|(key, _)| self.retains(key)) {
+            match self.rename.get(key.as_str()) {
+                Some(to) => renamed.push((to, value)),
+                None => {
+                    projected.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        for (key, value) in renamed {
+            projected.insert(key.as_str().into(), value.clone());
+        }
+        projected
+    }
+}
+
+/// Parse a comma-separated list of tag keys, which may contain a single `*` wildcard
+pub fn parse_keys(keys: &str) -> Result<Vec<Key>, Box<dyn Error>> {
+    let keys = keys.split(',').map(str::trim).filter(|key| !key.is_empty());
+    let keys =
+        keys.map(|key| Key::try_from(key.to_string()).map_err(|e| format!("{}: {}", e, key)));
+    Ok(keys.collect::<Result<_, _>>()?)
+}
+
+/// Parse a comma-separated list of `old=new` key renamings, no key may be renamed twice and no
+/// two keys alike
+pub fn parse_renames(renames: &str) -> Result<BTreeMap<String, String>, Box<dyn Error>> {
+    let mut mapping = BTreeMap::new();
+    for rename in renames.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+        match rename.splitn(2, '=').map(str::trim).collect::<Vec<_>>()[..] {
+            [from, to] if !from.is_empty() && !to.is_empty() => {
+                if mapping.values().any(|t| t == to) {
+                    return Err(format!("several tags renamed to '{}'", to).into());
+                }
+                if mapping.contains_key(from) {
+                    return Err(format!("tag '{}' renamed several times", from).into());
+                }
+                mapping.insert(from.to_string(), to.to_string());
+            }
+            _ => return Err(format!("invalid tag renaming '{}', expected old=new", rename).into()),
+        }
+    }
+    Ok(mapping)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::test_helpers::create_tags;
+    use super::*;
+
+    #[test]
+    fn project_tags() {
+        let original = create_tags(&[
+            ("addr:city", "Berlin"),
+            ("addr:street", "Karl-Marx-Allee"),
+            ("amenity", "cafe"),
+            ("building", "yes"),
+            ("name", "Cafe"),
+        ]);
+        assert!(TagProjection::default().is_identity());
+        assert_eq!(TagProjection::default().apply(&original), original);
+
+        let projection = TagProjection {
+            keep: parse_keys("name, amenity,addr:*").unwrap(),
+            drop: parse_keys("addr:city").unwrap(),
+            rename: parse_renames("amenity=category").unwrap(),
+        };
+        let expected = create_tags(&[
+            ("addr:street", "Karl-Marx-Allee"),
+            ("category", "cafe"),
+            ("name", "Cafe"),
+        ]);
+        assert_eq!(projection.apply(&original), expected);
+
+        let projection = TagProjection {
+            drop: parse_keys("*:street,building").unwrap(),
+            ..Default::default()
+        };
+        let expected = create_tags(&[
+            ("addr:city", "Berlin"),
+            ("amenity", "cafe"),
+            ("name", "Cafe"),
+        ]);
+        assert_eq!(projection.apply(&original), expected);
+
+        let projection = TagProjection {
+            keep: parse_keys("amenity,name").unwrap(),
+            rename: parse_renames("amenity=name,name=title").unwrap(),
+            ..Default::default()
+        };
+        let expected = create_tags(&[("name", "cafe"), ("title", "Cafe")]);
+        assert_eq!(projection.apply(&original), expected);
+
+        let projection = TagProjection {
+            keep: parse_keys("building,name").unwrap(),
+            rename: parse_renames("building=name").unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(projection.apply(&original), create_tags(&[("name", "yes")]));
+    }
+
+    #[test]
+    fn reject_invalid_projections() {
+        assert!(parse_keys("addr:*:*").is_err());
+        assert!(parse_renames("name").is_err());
+        assert!(parse_renames("name=").is_err());
+        assert!(parse_renames("name=title,alt_name=title").is_err());
+        assert!(parse_renames("name=title,name=label").is_err());
+        assert_eq!(parse_keys(" ").unwrap(), vec![]);
+    }
+}
@@ -1,8 +1,11 @@
 use geo::prelude::*;
 use geo::Closest;
 use geo_types::{Coordinate, Geometry, Line, LineString, MultiPoint, MultiPolygon, Point, Polygon};
+use polylabel::polylabel;
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 use std::convert::{TryFrom, TryInto};
+use std::str::FromStr;
 
 const EQ_PRECISION: f64 = 1.0e-5;
 
@@ -72,6 +75,36 @@ impl BoundaryGeometry {
             .any(|polygon| polygon.intersects(&geometry.line_string))
     }
 
+    pub fn contains_point(&self, lon: f64, lat: f64) -> bool {
+        self.multi_polygon.contains(&Point::new(lon, lat))
+    }
+
+    pub fn intersects_shape(&self, geometry: &Geometry<f64>) -> bool {
+        self.multi_polygon.0.iter().any(|polygon| match geometry {
+            Geometry::LineString(ls) => polygon.intersects(ls),
+            Geometry::Polygon(p) => polygon.intersects(p),
+            Geometry::MultiPolygon(mp) => mp.0.iter().any(|p| polygon.intersects(p)),
+            _ => false,
+        })
+    }
+
+    /// Whether the shape lies within the boundary, the polygons of a multi-polygon may be
+    /// contained in different polygons of the boundary
+    pub fn contains_shape(&self, geometry: &Geometry<f64>) -> bool {
+        let contains = |polygon: &Polygon<f64>| {
+            self.multi_polygon
+                .0
+                .iter()
+                .any(|boundary| boundary.contains(polygon))
+        };
+        match geometry {
+            Geometry::LineString(ls) => self.multi_polygon.0.iter().any(|p| p.contains(ls)),
+            Geometry::Polygon(p) => contains(p),
+            Geometry::MultiPolygon(mp) => !mp.0.is_empty() && mp.0.iter().all(contains),
+            _ => false,
+        }
+    }
+
     pub fn owns(&self, geometry: &SegmentGeometry) -> bool {
         if let Some(centroid) = geometry.line_string.centroid() {
             self.multi_polygon.contains(&centroid)
@@ -249,6 +282,42 @@ pub struct Bounds {
     w: f64,
 }
 
+impl Bounds {
+    pub fn new(w: f64, s: f64, e: f64, n: f64) -> Self {
+        Bounds { e, n, s, w }
+    }
+
+    pub fn contains_point(&self, lon: f64, lat: f64) -> bool {
+        lon >= self.w && lon <= self.e && lat >= self.s && lat <= self.n
+    }
+
+    pub fn contains(&self, other: &Bounds) -> bool {
+        other.w >= self.w && other.e <= self.e && other.s >= self.s && other.n <= self.n
+    }
+
+    pub fn intersects(&self, other: &Bounds) -> bool {
+        other.w <= self.e && other.e >= self.w && other.s <= self.n && other.n >= self.s
+    }
+}
+
+/// Parse bounds from a `w,s,e,n` string
+impl FromStr for Bounds {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|value| value.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| "bounds need to be given as numbers")?;
+        match values[..] {
+            [w, s, e, n] if w <= e && s <= n => Ok(Bounds::new(w, s, e, n)),
+            [_, _, _, _] => Err("bounds need to be given as w,s,e,n"),
+            _ => Err("bounds need to consist of four values"),
+        }
+    }
+}
+
 pub trait Midpoint {
     fn midpoint(&self) -> Option<(f64, f64)>;
 }
@@ -288,10 +357,11 @@ impl From<&Bounds> for (Location, Location) {
     }
 }
 
-fn get_geometry(coordinates: &[(f64, f64)]) -> Option<Geometry<f64>> {
+/// A polygon for closed sequences of coordinates, otherwise a line string
+pub fn get_geometry(coordinates: &[(f64, f64)]) -> Option<Geometry<f64>> {
     let line_string: LineString<f64> = coordinates.to_vec().into();
     let first = line_string.points_iter().next()?;
-    let last = line_string.points_iter().last()?;
+    let last = line_string.points_iter().next_back()?;
     if first == last {
         let polygon = Polygon::new(line_string, vec![]);
         Some(Geometry::Polygon(polygon))
@@ -304,6 +374,7 @@ fn get_bounds(geometry: &Geometry<f64>) -> Option<Bounds> {
     let rect = match geometry {
         Geometry::LineString(ls) => ls.bounding_rect(),
         Geometry::Polygon(p) => p.bounding_rect(),
+        Geometry::MultiPolygon(mp) => mp.bounding_rect(),
         _ => None,
     }?;
     Some(Bounds {
@@ -330,19 +401,54 @@ impl Centerable for Geometry<f64> {
         let point = match self {
             Geometry::LineString(ls) => ls.centroid(),
             Geometry::Polygon(p) => p.centroid(),
+            Geometry::MultiPolygon(mp) => mp.centroid(),
             _ => None,
         }?;
         Some(point.into())
     }
 }
 
-pub fn get_geo_info(coordinates: &[(f64, f64)]) -> (Option<Location>, Option<Bounds>) {
-    if let Some(geo) = get_geometry(coordinates) {
-        let centroid = geo.get_centroid();
-        let bounds = get_bounds(&geo);
-        return (centroid, bounds);
+pub fn get_geometry_info(geometry: &Geometry<f64>) -> (Option<Location>, Option<Bounds>) {
+    (geometry.get_centroid(), get_bounds(geometry))
+}
+
+/// A point inside of an area, unlike the centroid of concave shapes, found as the pole of
+/// inaccessibility of the polygon (the largest one of a multipolygon)
+pub fn get_point_on_surface(geometry: &Geometry<f64>) -> Option<Location> {
+    let polygon = match geometry {
+        Geometry::Polygon(p) => p,
+        Geometry::MultiPolygon(mp) => mp.0.iter().max_by(|a, b| {
+            let (a, b) = (a.unsigned_area(), b.unsigned_area());
+            a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+        })?,
+        _ => return None,
+    };
+    // degenerate rings (e.g. of ways clipped by the extract) have no inside
+    if polygon.unsigned_area() == 0. {
+        return None;
+    }
+    // the precision is relative to the size of the polygon, to bound the number of iterations
+    let rect = polygon.bounding_rect()?;
+    let tolerance = rect.width().max(rect.height()) / 1000.;
+    let point = polylabel(polygon, &tolerance).ok()?;
+    if !polygon.contains(&point) {
+        return None;
+    }
+    Some(point.into())
+}
+
+/// Area (m²) of polygons and length (m) of line strings, computed on a sphere approximating the
+/// earth (Chamberlain-Duquette and haversine)
+pub fn get_measures(geometry: &Geometry<f64>) -> (Option<f64>, Option<f64>) {
+    match geometry {
+        Geometry::Polygon(p) => (Some(p.chamberlain_duquette_unsigned_area()), None),
+        Geometry::MultiPolygon(mp) => {
+            let area = mp.0.iter().map(|p| p.chamberlain_duquette_unsigned_area());
+            (Some(area.sum()), None)
+        }
+        Geometry::LineString(ls) => (None, Some(ls.haversine_length())),
+        _ => (None, None),
     }
-    (None, None)
 }
 
 pub fn get_compound_coordinates(coordinates: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
@@ -393,9 +499,144 @@ mod tests {
     }
 
     #[test]
-    fn get_geo_info_open() {
+    fn parse_bounds() {
+        let bounds: Bounds = "13.3,52.4,13.5,52.6".parse().unwrap();
+        assert_eq!(bounds, Bounds::new(13.3, 52.4, 13.5, 52.6));
+        assert!("13.5,52.4,13.3,52.6".parse::<Bounds>().is_err());
+        assert!("13.3,52.4,13.5".parse::<Bounds>().is_err());
+        assert!("w,s,e,n".parse::<Bounds>().is_err());
+    }
+
+    #[test]
+    fn bounds_relations() {
+        let bounds = Bounds::new(5., 49., 7., 51.);
+        assert!(bounds.contains_point(6., 50.));
+        assert!(!bounds.contains_point(8., 50.));
+
+        let inner = Bounds::new(5.5, 49.5, 6.5, 50.5);
+        let overlapping = Bounds::new(6.5, 50.5, 8., 52.);
+        let outside = Bounds::new(8., 52., 9., 53.);
+        assert!(bounds.contains(&inner) && bounds.intersects(&inner));
+        assert!(!bounds.contains(&overlapping) && bounds.intersects(&overlapping));
+        assert!(!bounds.contains(&outside) && !bounds.intersects(&outside));
+    }
+
+    #[test]
+    fn boundary_geometry_relations() {
+        let exterior = vec![(5., 49.), (7., 49.), (7., 51.), (5., 51.), (5., 49.)];
+        let polygon = Polygon::new(exterior.into(), vec![]);
+        let geometry = BoundaryGeometry::new(MultiPolygon(vec![polygon])).unwrap();
+        assert!(geometry.contains_point(6., 50.));
+        assert!(!geometry.contains_point(8., 50.));
+
+        let shape = |coordinates: Vec<(f64, f64)>| get_geometry(&coordinates).unwrap();
+        let inner = shape(vec![(5.5, 49.5), (6.5, 49.5), (6.5, 50.5), (5.5, 49.5)]);
+        let crossing = shape(vec![(6., 50.), (8., 50.)]);
+        let outside = shape(vec![(8., 52.), (9., 52.), (9., 53.), (8., 52.)]);
+        assert!(geometry.contains_shape(&inner) && geometry.intersects_shape(&inner));
+        assert!(!geometry.contains_shape(&crossing) && geometry.intersects_shape(&crossing));
+        assert!(!geometry.contains_shape(&outside) && !geometry.intersects_shape(&outside));
+
+        let polygon = |shape| match shape {
+            Geometry::Polygon(p) => p,
+            _ => unreachable!(),
+        };
+        let parts = MultiPolygon(vec![polygon(inner.clone()), polygon(outside)]);
+        let parts = Geometry::MultiPolygon(parts);
+        assert!(!geometry.contains_shape(&parts) && geometry.intersects_shape(&parts));
+        let parts = Geometry::MultiPolygon(MultiPolygon(vec![polygon(inner)]));
+        assert!(geometry.contains_shape(&parts));
+        let empty = Geometry::MultiPolygon(MultiPolygon(vec![]));
+        assert!(!geometry.contains_shape(&empty) && !geometry.intersects_shape(&empty));
+    }
+
+    #[test]
+    fn get_measures_of_shapes() {
+        // a thousandth of a degree is roughly 111 m
+        let line = get_geometry(&[(13., 52.), (13., 52.001)]).unwrap();
+        let (area, length) = get_measures(&line);
+        assert_eq!(area, None);
+        assert_relative_eq!(length.unwrap(), 111.19, epsilon = 0.01);
+
+        let square = vec![(0., 0.), (0.001, 0.), (0.001, 0.001), (0., 0.001), (0., 0.)];
+        let (area, length) = get_measures(&get_geometry(&square).unwrap());
+        assert_eq!(length, None);
+        assert_relative_eq!(area.unwrap(), 12_392., epsilon = 1.);
+
+        let hole = vec![
+            (0., 0.),
+            (0.0005, 0.),
+            (0.0005, 0.0005),
+            (0., 0.0005),
+            (0., 0.),
+        ];
+        let shifted = square.iter().map(|(x, y)| (x + 1., *y)).collect::<Vec<_>>();
+        let multi_polygon = MultiPolygon(vec![
+            Polygon::new(square.into(), vec![hole.into()]),
+            Polygon::new(shifted.into(), vec![]),
+        ]);
+        let (area, _) = get_measures(&Geometry::MultiPolygon(multi_polygon));
+        assert_relative_eq!(area.unwrap(), 1.75 * 12_392., epsilon = 2.);
+    }
+
+    #[test]
+    fn get_geometry_info_of_multi_polygon() {
+        let square = vec![(5., 49.), (6., 49.), (6., 50.), (5., 50.), (5., 49.)];
+        let shifted = square.iter().map(|(x, y)| (x + 2., *y)).collect::<Vec<_>>();
+        let multi_polygon = MultiPolygon(vec![
+            Polygon::new(square.into(), vec![]),
+            Polygon::new(shifted.into(), vec![]),
+        ]);
+        let (centroid, bounds) = get_geometry_info(&Geometry::MultiPolygon(multi_polygon));
+        assert_eq!(
+            centroid.unwrap(),
+            Location {
+                lat: 49.5,
+                lon: 6.5
+            }
+        );
+        assert_eq!(bounds.unwrap(), Bounds::new(5., 49., 8., 50.));
+    }
+
+    #[test]
+    fn get_point_on_surface_of_concave_polygons() {
+        // U-shaped polygon, whose centroid lies in the gap
+        let u_shape = vec![
+            (0., 0.),
+            (3., 0.),
+            (3., 3.),
+            (2., 3.),
+            (2., 1.),
+            (1., 1.),
+            (1., 3.),
+            (0., 3.),
+            (0., 0.),
+        ];
+        let polygon = Polygon::new(u_shape.into(), vec![]);
+        let geometry = Geometry::Polygon(polygon.clone());
+        let centroid = geometry.get_centroid().unwrap();
+        assert!(!polygon.contains(&Point::new(centroid.lon, centroid.lat)));
+        let point = get_point_on_surface(&geometry).unwrap();
+        assert!(polygon.contains(&Point::new(point.lon, point.lat)));
+
+        let small = vec![(10., 0.), (10.5, 0.), (10.5, 0.5), (10., 0.)];
+        let small = Polygon::new(small.into(), vec![]);
+        let multi_polygon = MultiPolygon(vec![small, polygon.clone()]);
+        let point = get_point_on_surface(&Geometry::MultiPolygon(multi_polygon)).unwrap();
+        assert!(polygon.contains(&Point::new(point.lon, point.lat)));
+
+        let line = get_geometry(&[(5., 49.), (6., 50.)]).unwrap();
+        assert_eq!(get_point_on_surface(&line), None);
+
+        // a closed way going back and forth, A → B → A
+        let degenerate = get_geometry(&[(13.41, 52.51), (13.42, 52.52), (13.41, 52.51)]).unwrap();
+        assert_eq!(get_point_on_surface(&degenerate), None);
+    }
+
+    #[test]
+    fn get_geometry_info_open() {
         let coordinates = vec![(5., 49.), (6., 50.), (7., 49.)];
-        let (centroid, bounds) = get_geo_info(&coordinates);
+        let (centroid, bounds) = get_geometry_info(&get_geometry(&coordinates).unwrap());
         let reference_loc = Location { lat: 49.5, lon: 6. };
         assert_eq!(centroid.unwrap(), reference_loc);
         let reference_bounds = Bounds {
@@ -408,9 +649,9 @@ mod tests {
     }
 
     #[test]
-    fn get_geo_info_closed() {
+    fn get_geometry_info_closed() {
         let coordinates = vec![(5., 49.), (6., 50.), (7., 49.), (5., 49.)];
-        let (centroid, bounds) = get_geo_info(&coordinates);
+        let (centroid, bounds) = get_geometry_info(&get_geometry(&coordinates).unwrap());
         let reference_loc = Location {
             lat: 49.333_333,
             lon: 6.,
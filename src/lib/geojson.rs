@@ -1,15 +1,85 @@
+use geo_types::{LineString, MultiPolygon, Polygon};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::error::Error;
 
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum Geometry {
+    Point {
+        coordinates: (f64, f64),
+    },
+    MultiPoint {
+        coordinates: Vec<(f64, f64)>,
+    },
+    LineString {
+        coordinates: Vec<(f64, f64)>,
+    },
     MultiLineString {
         coordinates: Vec<Vec<(f64, f64)>>,
     },
+    Polygon {
+        coordinates: Vec<Vec<(f64, f64)>>,
+    },
     MultiPolygon {
         coordinates: Vec<Vec<Vec<(f64, f64)>>>,
     },
+    #[serde(rename = "GeometryCollection")]
+    Collection {
+        geometries: Vec<Geometry>,
+    },
+}
+
+fn ring_coordinates(line_string: &LineString<f64>) -> Vec<(f64, f64)> {
+    line_string.0.iter().map(|c| (c.x, c.y)).collect()
+}
+
+fn polygon_coordinates(polygon: &Polygon<f64>) -> Vec<Vec<(f64, f64)>> {
+    let exterior = std::iter::once(polygon.exterior());
+    exterior
+        .chain(polygon.interiors())
+        .map(ring_coordinates)
+        .collect()
+}
+
+/// Lines, rectangles and triangles are represented as line strings and polygons respectively
+impl From<&geo_types::Geometry<f64>> for Geometry {
+    fn from(geometry: &geo_types::Geometry<f64>) -> Self {
+        use geo_types::Geometry as Geo;
+
+        match geometry {
+            Geo::Point(p) => Geometry::Point {
+                coordinates: (p.x(), p.y()),
+            },
+            Geo::Line(line) => Geometry::LineString {
+                coordinates: vec![line.start.x_y(), line.end.x_y()],
+            },
+            Geo::LineString(ls) => Geometry::LineString {
+                coordinates: ring_coordinates(ls),
+            },
+            Geo::Polygon(p) => Geometry::Polygon {
+                coordinates: polygon_coordinates(p),
+            },
+            Geo::MultiPoint(mp) => Geometry::MultiPoint {
+                coordinates: mp.0.iter().map(|p| (p.x(), p.y())).collect(),
+            },
+            Geo::MultiLineString(mls) => Geometry::MultiLineString {
+                coordinates: mls.0.iter().map(ring_coordinates).collect(),
+            },
+            Geo::MultiPolygon(mp) => Geometry::MultiPolygon {
+                coordinates: mp.0.iter().map(polygon_coordinates).collect(),
+            },
+            Geo::GeometryCollection(gc) => Geometry::Collection {
+                geometries: gc.0.iter().map(Geometry::from).collect(),
+            },
+            Geo::Rect(rect) => Geometry::Polygon {
+                coordinates: polygon_coordinates(&rect.to_polygon()),
+            },
+            Geo::Triangle(triangle) => Geometry::Polygon {
+                coordinates: polygon_coordinates(&triangle.to_polygon()),
+            },
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -17,9 +87,136 @@ pub enum Geometry {
 pub enum Entity {
     Feature {
         properties: HashMap<String, String>,
-        geometry: Geometry,
+        geometry: Option<Geometry>,
     },
     FeatureCollection {
         features: Vec<Entity>,
     },
 }
+
+fn to_polygon(rings: &[Vec<Vec<f64>>]) -> Polygon<f64> {
+    let mut line_strings = rings.iter().map(|ring| {
+        let coordinates: Vec<(f64, f64)> = ring
+            .iter()
+            .filter(|position| position.len() >= 2)
+            .map(|position| (position[0], position[1]))
+            .collect();
+        LineString::from(coordinates)
+    });
+    let exterior = line_strings.next().unwrap_or_else(|| LineString(vec![]));
+    Polygon::new(exterior, line_strings.collect())
+}
+
+fn collect_polygons(value: &::geojson::Value, polygons: &mut Vec<Polygon<f64>>) {
+    use ::geojson::Value;
+
+    match value {
+        Value::Polygon(rings) => polygons.push(to_polygon(rings)),
+        Value::MultiPolygon(multi) => polygons.extend(multi.iter().map(|rings| to_polygon(rings))),
+        Value::GeometryCollection(geometries) => {
+            for geometry in geometries {
+                collect_polygons(&geometry.value, polygons);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Read all (Multi)Polygons of a GeoJSON document, other geometries are ignored
+pub fn read_multi_polygon(json: &str) -> Result<MultiPolygon<f64>, Box<dyn Error>> {
+    use ::geojson::GeoJson;
+
+    let geojson: GeoJson = json.parse()?;
+    let geometries = match &geojson {
+        GeoJson::Geometry(geometry) => vec![geometry],
+        GeoJson::Feature(feature) => feature.geometry.iter().collect(),
+        GeoJson::FeatureCollection(collection) => collection
+            .features
+            .iter()
+            .filter_map(|feature| feature.geometry.as_ref())
+            .collect(),
+    };
+    let mut polygons = vec![];
+    for geometry in geometries {
+        collect_polygons(&geometry.value, &mut polygons);
+    }
+    if polygons.is_empty() {
+        return Err("GeoJSON does not contain any polygons".into());
+    }
+    Ok(MultiPolygon(polygons))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_polygons() {
+        let json = r#"{
+            "type": "FeatureCollection",
+            "features": [{
+                "type": "Feature",
+                "properties": { "name": "a", "admin_level": 9 },
+                "geometry": {
+                    "type": "Polygon",
+                    "coordinates": [[[0, 0], [1, 0], [1, 1], [0, 0]]]
+                }
+            }, {
+                "type": "Feature",
+                "properties": null,
+                "geometry": {
+                    "type": "MultiPolygon",
+                    "coordinates": [
+                        [[[2, 2], [3, 2], [3, 3], [2, 2]]],
+                        [[[4, 4], [5, 4], [5, 5], [4, 4]]]
+                    ]
+                }
+            }, {
+                "type": "Feature",
+                "properties": null,
+                "geometry": { "type": "Point", "coordinates": [9, 9] }
+            }]
+        }"#;
+        let multi_polygon = read_multi_polygon(json).unwrap();
+        assert_eq!(multi_polygon.0.len(), 3);
+        let exterior = multi_polygon.0[0].exterior();
+        assert_eq!(exterior.num_coords(), 4);
+    }
+
+    #[test]
+    fn convert_geometries() {
+        use geo_types::{Geometry as Geo, GeometryCollection, Point, Rect};
+
+        let square = vec![(0., 0.), (1., 0.), (1., 1.), (0., 0.)];
+        let hole = vec![(0.2, 0.1), (0.8, 0.1), (0.8, 0.7), (0.2, 0.1)];
+        let polygon = Polygon::new(square.clone().into(), vec![hole.clone().into()]);
+        let collection = GeometryCollection(vec![
+            Geo::Point(Point::new(1., 2.)),
+            Geo::LineString(square.clone().into()),
+            Geo::MultiPolygon(MultiPolygon(vec![polygon])),
+            Geo::Rect(Rect::new((0., 0.), (1., 1.))),
+        ]);
+        let geometry: Geometry = (&Geo::GeometryCollection(collection)).into();
+        let json = serde_json::to_value(geometry).unwrap();
+        let expected = serde_json::json!({
+            "type": "GeometryCollection",
+            "geometries": [
+                { "type": "Point", "coordinates": [1., 2.] },
+                { "type": "LineString", "coordinates": square },
+                { "type": "MultiPolygon", "coordinates": [[square, hole]] },
+                {
+                    "type": "Polygon",
+                    "coordinates": [[[0., 0.], [0., 1.], [1., 1.], [1., 0.], [0., 0.]]]
+                },
+            ]
+        });
+        assert_eq!(json, expected);
+    }
+
+    #[test]
+    fn read_without_polygons() {
+        let json = r#"{ "type": "Point", "coordinates": [9, 9] }"#;
+        assert!(read_multi_polygon(json).is_err());
+        assert!(read_multi_polygon("not json").is_err());
+    }
+}
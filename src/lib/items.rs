@@ -1,4 +1,5 @@
-use super::geo::{BoundaryGeometry, SegmentGeometry};
+use super::geo::{BoundaryGeometry, Location, SegmentGeometry};
+use serde::Serialize;
 
 pub struct AdminBoundary {
     pub name: String,
@@ -19,10 +20,98 @@ pub struct Segment {
     pub geometry: SegmentGeometry,
 }
 
+/// Address of a node, way or relation, as given by its `addr:*` tags
+#[derive(Serialize, Debug)]
+pub struct Address {
+    pub id: i64,
+    #[serde(rename = "type")]
+    pub osm_type: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub street: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub place: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub housenumbers: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub housename: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub postcode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+    pub location: Option<Location>,
+}
+
 pub mod osm {
-    use super::super::geo::{get_geo_info, Bounds, Location};
+    pub use super::super::geo::Bounds;
+    use super::super::geo::{get_geometry_info, get_point_on_surface, BoundaryGeometry, Location};
+    use super::super::geojson::read_multi_polygon;
+    use geo_types::Geometry;
     use osmpbfreader::objects::Tags;
     use serde::{Deserialize, Serialize};
+    use std::error::Error;
+
+    /// Spatial relation an object needs to have with an area to be selected
+    ///
+    /// `Centroid` only considers an object's location or centroid, the other predicates
+    /// take its full geometry into account.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum SpatialPredicate {
+        Intersects,
+        Within,
+        Centroid,
+    }
+
+    /// Area to restrict extracted objects to
+    ///
+    /// A `Boundary` refers to an administrative boundary by name, which is looked up in the
+    /// same PBF the objects are extracted from.
+    pub enum Area {
+        Bounds(Bounds),
+        Boundary(String),
+        Polygon(BoundaryGeometry),
+    }
+
+    impl Area {
+        /// Read an area from the (Multi)Polygons in a GeoJSON document
+        pub fn from_geojson(json: &str) -> Result<Self, Box<dyn Error>> {
+            let multi_polygon = read_multi_polygon(json)?;
+            let geometry = BoundaryGeometry::new(multi_polygon)?;
+            Ok(Area::Polygon(geometry))
+        }
+
+        fn contains_point(&self, lon: f64, lat: f64) -> bool {
+            match self {
+                Area::Bounds(bounds) => bounds.contains_point(lon, lat),
+                Area::Polygon(geometry) => geometry.contains_point(lon, lat),
+                Area::Boundary(_) => false,
+            }
+        }
+
+        /// Test an object's geo info and geometry against the area
+        ///
+        /// An unresolved `Boundary` never matches, neither does a shape without geometry.
+        pub fn matches(
+            &self,
+            predicate: SpatialPredicate,
+            geo_info: &GeoInfo,
+            geometry: Option<&Geometry<f64>>,
+        ) -> bool {
+            match (self, geo_info, predicate) {
+                (_, GeoInfo::Point { lon, lat }, _) => self.contains_point(*lon, *lat),
+                (_, GeoInfo::Shape { centroid, .. }, SpatialPredicate::Centroid) => centroid
+                    .as_ref()
+                    .is_some_and(|loc| self.contains_point(loc.lon, loc.lat)),
+                (Area::Bounds(bounds), _, _) => geo_info.matches_bounds(bounds, predicate),
+                (Area::Polygon(boundary), _, SpatialPredicate::Intersects) => {
+                    geometry.is_some_and(|shape| boundary.intersects_shape(shape))
+                }
+                (Area::Polygon(boundary), _, SpatialPredicate::Within) => {
+                    geometry.is_some_and(|shape| boundary.contains_shape(shape))
+                }
+                (Area::Boundary(_), _, _) => false,
+            }
+        }
+    }
 
     #[derive(Serialize, Deserialize)]
     #[serde(untagged)]
@@ -33,22 +122,99 @@ pub mod osm {
         },
         Shape {
             centroid: Option<Location>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            point_on_surface: Option<Location>,
             bounds: Option<Bounds>,
             #[serde(skip_serializing_if = "Option::is_none")]
+            area_m2: Option<f64>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            length_m: Option<f64>,
+            #[serde(skip_serializing_if = "Option::is_none")]
             coordinates: Option<Vec<(f64, f64)>>,
         },
     }
 
     impl GeoInfo {
-        pub fn new_shape(coordinates: &[(f64, f64)], retain_coordinates: bool) -> Self {
-            let (centroid, bounds) = get_geo_info(coordinates);
+        /// Centroid and bounds are derived from the geometry, the coordinates are only retained
+        pub fn new_shape(
+            geometry: Option<&Geometry<f64>>,
+            coordinates: &[(f64, f64)],
+            retain_coordinates: bool,
+        ) -> Self {
+            let (centroid, bounds) = geometry.map_or((None, None), get_geometry_info);
             let coordinates = retain_coordinates.then(|| coordinates.into());
             GeoInfo::Shape {
                 centroid,
+                point_on_surface: None,
                 bounds,
+                area_m2: None,
+                length_m: None,
                 coordinates,
             }
         }
+
+        /// Add a point on the surface of an area, which unlike the centroid is guaranteed to
+        /// lie inside of it
+        pub fn with_point_on_surface(mut self, geometry: Option<&Geometry<f64>>) -> Self {
+            if let GeoInfo::Shape {
+                point_on_surface, ..
+            } = &mut self
+            {
+                *point_on_surface = geometry.and_then(get_point_on_surface);
+            }
+            self
+        }
+
+        /// Add the area (m²) of an area or the length (m) of a line
+        pub fn with_measures(mut self, area: Option<f64>, length: Option<f64>) -> Self {
+            if let GeoInfo::Shape {
+                area_m2, length_m, ..
+            } = &mut self
+            {
+                *area_m2 = area;
+                *length_m = length;
+            }
+            self
+        }
+
+        pub fn matches_bounds(&self, area: &Bounds, predicate: SpatialPredicate) -> bool {
+            match self {
+                GeoInfo::Point { lon, lat } => area.contains_point(*lon, *lat),
+                GeoInfo::Shape {
+                    bounds: Some(bounds),
+                    ..
+                } => match predicate {
+                    SpatialPredicate::Intersects => area.intersects(bounds),
+                    SpatialPredicate::Within => area.contains(bounds),
+                    SpatialPredicate::Centroid => match self {
+                        GeoInfo::Shape {
+                            centroid: Some(loc),
+                            ..
+                        } => area.contains_point(loc.lon, loc.lat),
+                        _ => false,
+                    },
+                },
+                GeoInfo::Shape { bounds: None, .. } => false,
+            }
+        }
+    }
+
+    /// Reference to a filter group, by its label or else its position in the expression
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    #[serde(untagged)]
+    pub enum GroupRef {
+        Label(String),
+        Index(usize),
+    }
+
+    /// Member of a relation, referenced by element type and id
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct Member {
+        #[serde(rename = "type")]
+        pub osm_type: &'static str,
+        #[serde(rename = "ref")]
+        pub id: i64,
+        pub role: String,
     }
 
     #[derive(Serialize, Deserialize)]
@@ -57,8 +223,16 @@ pub mod osm {
         #[serde(rename = "type")]
         osm_type: &'static str,
         tags: Tags,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        groups: Option<Vec<GroupRef>>,
         #[serde(flatten)]
         geo_info: GeoInfo,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        members: Option<Vec<Member>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        nodes: Option<Vec<i64>>,
+        #[serde(skip)]
+        geometry: Option<Geometry<f64>>,
     }
 
     impl Object {
@@ -67,9 +241,66 @@ pub mod osm {
                 id,
                 osm_type,
                 tags,
+                groups: None,
                 geo_info,
+                members: None,
+                nodes: None,
+                geometry: None,
             }
         }
+
+        /// Attach the filter groups the object matched
+        pub fn with_groups(self, groups: Option<Vec<GroupRef>>) -> Self {
+            Self { groups, ..self }
+        }
+
+        /// Attach the members of a relation
+        pub fn with_members(self, members: Option<Vec<Member>>) -> Self {
+            Self { members, ..self }
+        }
+
+        /// Attach the node ids of a way
+        pub fn with_nodes(self, nodes: Option<Vec<i64>>) -> Self {
+            Self { nodes, ..self }
+        }
+
+        /// Attach the full geometry of the object, which is not part of its JSON representation
+        pub fn with_geometry(self, geometry: Option<Geometry<f64>>) -> Self {
+            Self { geometry, ..self }
+        }
+
+        pub fn id(&self) -> i64 {
+            self.id
+        }
+
+        pub fn osm_type(&self) -> &'static str {
+            self.osm_type
+        }
+
+        pub fn tags(&self) -> &Tags {
+            &self.tags
+        }
+
+        pub fn geo_info(&self) -> &GeoInfo {
+            &self.geo_info
+        }
+
+        pub fn geometry(&self) -> Option<&Geometry<f64>> {
+            self.geometry.as_ref()
+        }
+
+        pub fn members(&self) -> Option<&[Member]> {
+            self.members.as_deref()
+        }
+
+        pub fn nodes(&self) -> Option<&[i64]> {
+            self.nodes.as_deref()
+        }
+
+        /// Filter groups the object matched, if they have been reported
+        pub fn groups(&self) -> Option<&[GroupRef]> {
+            self.groups.as_deref()
+        }
     }
 
     #[derive(Serialize, Deserialize)]
@@ -0,0 +1,230 @@
+use memmap2::MmapMut;
+use osmpbfreader::objects::{Node, NodeId, OsmId, OsmObj};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::{TryFrom, TryInto};
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Source of node coordinates for resolving the geometry of ways
+pub trait NodeLocations {
+    /// Longitude and latitude of a node
+    fn location(&self, id: NodeId) -> Option<(f64, f64)>;
+}
+
+impl NodeLocations for BTreeMap<OsmId, OsmObj> {
+    fn location(&self, id: NodeId) -> Option<(f64, f64)> {
+        let node = self.get(&id.into())?.node()?;
+        Some((node.lon(), node.lat()))
+    }
+}
+
+const ENTRY_SIZE: u64 = 8;
+const GROWTH: u64 = 1 << 24;
+
+/// Node locations in a file mapped into memory, indexed by node id
+///
+/// Each location takes 8 bytes at an offset given by the node's id, the file is grown as
+/// higher ids are inserted. It is sparse on most file systems, so it only occupies disk space
+/// for the id ranges actually present. The file must not exist yet, so that no other file is
+/// overwritten, and it is removed when the store is dropped.
+///
+/// Nodes with negative ids, as found in files edited with JOSM, are rare and kept in memory.
+pub struct LocationStore {
+    path: PathBuf,
+    file: File,
+    mmap: MmapMut,
+    capacity: u64,
+    negative: HashMap<i64, (i32, i32)>,
+}
+
+impl LocationStore {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Self::with_capacity(path, GROWTH)
+    }
+
+    /// The file is removed again if it can not be prepared for the given number of entries
+    fn with_capacity(path: &Path, capacity: u64) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::AlreadyExists => io::Error::new(
+                    e.kind(),
+                    format!("node store {} already exists", path.display()),
+                ),
+                _ => e,
+            })?;
+        match Self::map(&file, capacity) {
+            Ok(mmap) => Ok(LocationStore {
+                path: path.to_path_buf(),
+                file,
+                mmap,
+                capacity,
+                negative: HashMap::new(),
+            }),
+            Err(e) => {
+                let _ = fs::remove_file(path);
+                Err(e)
+            }
+        }
+    }
+
+    fn map(file: &File, capacity: u64) -> io::Result<MmapMut> {
+        let len = capacity
+            .checked_mul(ENTRY_SIZE)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "node store too large"))?;
+        file.set_len(len)?;
+        // the file is private to the store and not modified by other means while it is mapped
+        unsafe { MmapMut::map_mut(file) }
+    }
+
+    /// Store the location of a node
+    pub fn insert(&mut self, node: &Node) -> io::Result<()> {
+        // the sign bits are flipped, so that unwritten entries do not decode to a location
+        let lon = node.decimicro_lon ^ i32::MIN;
+        let lat = node.decimicro_lat ^ i32::MIN;
+        let index = match u64::try_from(node.id.0) {
+            Ok(index) => index,
+            Err(_) => {
+                self.negative.insert(node.id.0, (lon, lat));
+                return Ok(());
+            }
+        };
+        if index >= self.capacity {
+            self.grow(index + 1)?;
+        }
+        let offset = (index * ENTRY_SIZE) as usize;
+        self.mmap[offset..offset + 4].copy_from_slice(&lon.to_le_bytes());
+        self.mmap[offset + 4..offset + 8].copy_from_slice(&lat.to_le_bytes());
+        Ok(())
+    }
+
+    fn grow(&mut self, required: u64) -> io::Result<()> {
+        let capacity = (required / GROWTH + 1) * GROWTH;
+        self.mmap = Self::map(&self.file, capacity)?;
+        self.capacity = capacity;
+        Ok(())
+    }
+
+    /// Encoded location at the given index of the file, if one has been written
+    fn entry(&self, index: u64) -> Option<(i32, i32)> {
+        if index >= self.capacity {
+            return None;
+        }
+        let offset = (index * ENTRY_SIZE) as usize;
+        let entry = &self.mmap[offset..offset + 8];
+        let lon = i32::from_le_bytes(entry[..4].try_into().ok()?);
+        let lat = i32::from_le_bytes(entry[4..].try_into().ok()?);
+        if lon == 0 && lat == 0 {
+            return None;
+        }
+        Some((lon, lat))
+    }
+}
+
+impl NodeLocations for LocationStore {
+    fn location(&self, id: NodeId) -> Option<(f64, f64)> {
+        let (lon, lat) = match u64::try_from(id.0) {
+            Ok(index) => self.entry(index)?,
+            Err(_) => *self.negative.get(&id.0)?,
+        };
+        // decoded like `Node::lon` and `Node::lat`
+        let decode = |value: i32| (value ^ i32::MIN) as f64 * 1e-7;
+        Some((decode(lon), decode(lat)))
+    }
+}
+
+impl Drop for LocationStore {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: i64, lon: f64, lat: f64) -> Node {
+        Node {
+            id: NodeId(id),
+            tags: Default::default(),
+            decimicro_lat: (lat * 1e7) as i32,
+            decimicro_lon: (lon * 1e7) as i32,
+        }
+    }
+
+    /// Paths are unique per process, so that concurrent test runs do not share files
+    fn temp_path(name: &str) -> PathBuf {
+        let file_name = format!("osm_pbf2json_{}_{}", name, std::process::id());
+        std::env::temp_dir().join(file_name)
+    }
+
+    #[test]
+    fn store_and_look_up_locations() {
+        let path = temp_path("store_and_look_up_locations");
+        let _ = fs::remove_file(&path);
+        let mut store = LocationStore::create(&path).unwrap();
+        let nodes = [
+            node(1, 13.4, 52.5),
+            node(2, 0., 0.),
+            node(3, -180., -90.),
+            node(GROWTH as i64 * 2 + 5, 180., 90.),
+        ];
+        for n in nodes.iter() {
+            store.insert(n).unwrap();
+        }
+
+        for n in nodes.iter() {
+            assert_eq!(store.location(n.id), Some((n.lon(), n.lat())));
+        }
+        assert_eq!(store.location(NodeId(4)), None);
+        assert_eq!(store.location(NodeId(-1)), None);
+        assert_eq!(store.location(NodeId(GROWTH as i64 * 5)), None);
+
+        drop(store);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn store_negative_ids() {
+        let path = temp_path("store_negative_ids");
+        let _ = fs::remove_file(&path);
+        let mut store = LocationStore::create(&path).unwrap();
+        let negative = node(-1, 1., 1.);
+        store.insert(&negative).unwrap();
+        store.insert(&node(1, 2., 2.)).unwrap();
+        assert_eq!(
+            store.location(NodeId(-1)),
+            Some((negative.lon(), negative.lat()))
+        );
+        assert_eq!(store.location(NodeId(-2)), None);
+        assert_eq!(store.location(NodeId(1)), Some((2., 2.)));
+
+        drop(store);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn keep_existing_files() {
+        let path = temp_path("keep_existing_files");
+        fs::write(&path, "data").unwrap();
+        let error = LocationStore::create(&path).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "data");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn remove_file_on_failure() {
+        let path = temp_path("remove_file_on_failure");
+        let _ = fs::remove_file(&path);
+        // the file would exceed the largest possible file size
+        let capacity = i64::MAX as u64 / ENTRY_SIZE + 1;
+        assert!(LocationStore::with_capacity(&path, capacity).is_err());
+        assert!(!path.exists());
+        assert!(LocationStore::create(&path).is_ok());
+    }
+}
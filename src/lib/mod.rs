@@ -1,47 +1,53 @@
 //! A parser/filter for OSM protobuf bundles.
 
-use self::geo::get_compound_coordinates;
-use self::items::{osm, AdminBoundary, Street};
+use self::geo::{get_compound_coordinates, get_geometry, get_measures};
+use self::items::osm::{Area, SpatialPredicate};
+use self::items::{osm, Address, AdminBoundary, Street};
+use addresses::extract_address;
 use admin::get_boundaries;
-use filter::{Condition, Filter, Group};
-use osmpbfreader::objects::{OsmId, OsmObj, Relation, RelationId, Way};
+use filter::{CompiledFilter, Condition, Filter, Group, Measures, MemberIndex, TagProjection};
+use geo_types::{Geometry, GeometryCollection, MultiPolygon, Point};
+use locations::{LocationStore, NodeLocations};
+use osm_boundaries_utils::build_boundary;
+use osmpbfreader::objects::{Node, OsmId, OsmObj, Ref, Relation, RelationId, Tags, Way};
 use osmpbfreader::OsmPbfReader;
 use rstar::RTree;
-use std::collections::BTreeMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
 use std::error::Error;
 use std::io::{Read, Seek};
+use std::path::Path;
 use streets::extract_streets;
 
+mod addresses;
 mod admin;
 pub mod filter;
 mod geo;
 mod geojson;
 pub mod items;
+mod locations;
 pub mod output;
 mod streets;
 mod test_helpers;
 
 trait OsmExt {
-    fn get_coordinates(&self, objs: &BTreeMap<OsmId, OsmObj>) -> Vec<(f64, f64)>;
+    fn get_coordinates(&self, locations: &dyn NodeLocations) -> Vec<(f64, f64)>;
 }
 
 trait OsmCycle {
     fn get_coordinates(
         &self,
         objs: &BTreeMap<OsmId, OsmObj>,
+        locations: &dyn NodeLocations,
         visited: &mut Vec<RelationId>,
     ) -> Vec<(f64, f64)>;
 }
 
 impl OsmExt for Way {
-    fn get_coordinates(&self, objs: &BTreeMap<OsmId, OsmObj>) -> Vec<(f64, f64)> {
+    fn get_coordinates(&self, locations: &dyn NodeLocations) -> Vec<(f64, f64)> {
         self.nodes
             .iter()
-            .filter_map(|&id| {
-                let obj = objs.get(&id.into())?;
-                let node = obj.node()?;
-                Some((node.lon(), node.lat()))
-            })
+            .filter_map(|&id| locations.location(id))
             .collect()
     }
 }
@@ -50,6 +56,7 @@ impl OsmCycle for Relation {
     fn get_coordinates(
         &self,
         objs: &BTreeMap<OsmId, OsmObj>,
+        locations: &dyn NodeLocations,
         visited: &mut Vec<RelationId>,
     ) -> Vec<(f64, f64)> {
         if visited.contains(&self.id) {
@@ -60,11 +67,13 @@ impl OsmCycle for Relation {
             .refs
             .iter()
             .filter_map(|osm_ref| {
-                let obj = objs.get(&osm_ref.member)?;
-                let coordinates = match obj {
-                    OsmObj::Node(node) => vec![(node.lon(), node.lat())],
-                    OsmObj::Way(way) => way.get_coordinates(objs),
-                    OsmObj::Relation(rel) => rel.get_coordinates(objs, visited),
+                if let OsmId::Node(id) = osm_ref.member {
+                    return Some(locations.location(id).into_iter().collect());
+                }
+                let coordinates = match objs.get(&osm_ref.member)? {
+                    OsmObj::Way(way) => way.get_coordinates(locations),
+                    OsmObj::Relation(rel) => rel.get_coordinates(objs, locations, visited),
+                    OsmObj::Node(_) => vec![],
                 };
                 Some(coordinates)
             })
@@ -74,6 +83,88 @@ impl OsmCycle for Relation {
     }
 }
 
+/// Outer and inner rings of a `type=multipolygon` relation, assembled from its way members
+fn get_multi_polygon(
+    relation: &Relation,
+    objs: &BTreeMap<OsmId, OsmObj>,
+    locations: &dyn NodeLocations,
+) -> Option<MultiPolygon<f64>> {
+    if !relation.tags.contains("type", "multipolygon") {
+        return None;
+    }
+    // the rings are built from nodes, those not kept are recreated from their locations
+    let mut parts: BTreeMap<OsmId, Cow<OsmObj>> = BTreeMap::new();
+    for osm_ref in relation.refs.iter() {
+        let way = match objs.get(&osm_ref.member) {
+            Some(obj @ OsmObj::Way(_)) => obj,
+            _ => continue,
+        };
+        for &id in way.way()?.nodes.iter() {
+            if let Some(node) = objs.get(&id.into()) {
+                parts.insert(id.into(), Cow::Borrowed(node));
+            } else if let Some((lon, lat)) = locations.location(id) {
+                let node = Node {
+                    id,
+                    tags: Tags::new(),
+                    decimicro_lat: (lat * 1e7).round() as i32,
+                    decimicro_lon: (lon * 1e7).round() as i32,
+                };
+                parts.insert(id.into(), Cow::Owned(node.into()));
+            }
+        }
+        parts.insert(osm_ref.member, Cow::Borrowed(way));
+    }
+    build_boundary(relation, &parts).filter(|multi_polygon| !multi_polygon.0.is_empty())
+}
+
+fn get_member(reference: &Ref) -> osm::Member {
+    let (osm_type, id) = match reference.member {
+        OsmId::Node(id) => ("node", id.0),
+        OsmId::Way(id) => ("way", id.0),
+        OsmId::Relation(id) => ("relation", id.0),
+    };
+    osm::Member {
+        osm_type,
+        id,
+        role: reference.role.to_string(),
+    }
+}
+
+/// Geometries of a relation's members, those of nested relations are included recursively
+fn get_member_geometries(
+    relation: &Relation,
+    objs: &BTreeMap<OsmId, OsmObj>,
+    locations: &dyn NodeLocations,
+    visited: &mut Vec<RelationId>,
+) -> Vec<Geometry<f64>> {
+    if visited.contains(&relation.id) {
+        return vec![];
+    }
+    visited.push(relation.id);
+    relation
+        .refs
+        .iter()
+        .flat_map(|osm_ref| {
+            if let OsmId::Node(id) = osm_ref.member {
+                let point = locations
+                    .location(id)
+                    .map(|(lon, lat)| Point::new(lon, lat));
+                return point.map(Geometry::Point).into_iter().collect();
+            }
+            match objs.get(&osm_ref.member) {
+                Some(OsmObj::Way(way)) => get_geometry(&way.get_coordinates(locations))
+                    .into_iter()
+                    .collect(),
+                Some(OsmObj::Relation(rel)) => match get_multi_polygon(rel, objs, locations) {
+                    Some(multi_polygon) => vec![Geometry::MultiPolygon(multi_polygon)],
+                    None => get_member_geometries(rel, objs, locations, visited),
+                },
+                _ => vec![],
+            }
+        })
+        .collect()
+}
+
 fn build_admin_group(levels: Vec<u8>) -> Vec<Group> {
     levels
         .iter()
@@ -86,6 +177,13 @@ fn build_admin_group(levels: Vec<u8>) -> Vec<Group> {
         .collect()
 }
 
+fn build_boundary_group(name: &str) -> Vec<Group> {
+    let name_match = Condition::new("name", Some(name));
+    let boundary_match = Condition::new("boundary", Some("administrative"));
+    let conditions = vec![boundary_match, name_match];
+    vec![Group { conditions }]
+}
+
 fn build_street_group(name: Option<&str>) -> Vec<Group> {
     let values = vec![
         "primary",
@@ -97,13 +195,18 @@ fn build_street_group(name: Option<&str>) -> Vec<Group> {
         "pedestrian",
     ];
 
+    let values = values.into_iter().map(|val| val.into()).collect();
+    let highway_match = Condition::ValueAlternatives("highway".into(), values);
     let name_condition = Condition::new("name", name);
-    values
-        .into_iter()
-        .map(|val| {
-            let highway_match = Condition::new("highway", Some(val));
-            let conditions = vec![highway_match, name_condition.clone()];
-            Group { conditions }
+    let conditions = vec![highway_match, name_condition];
+    vec![Group { conditions }]
+}
+
+fn build_address_groups() -> Vec<Group> {
+    let keys = vec!["addr:housenumber", "addr:housename"];
+    keys.into_iter()
+        .map(|key| Group {
+            conditions: vec![Condition::new(key, None)],
         })
         .collect()
 }
@@ -153,18 +256,37 @@ pub fn boundaries(
 ///
 /// let file = File::open("./tests/data/wilhelmstrasse.pbf").unwrap();
 /// let name = "Wilhelmstraße";
-/// let streets = streets(file, Some(name), Some(10)).unwrap();
+/// let streets = streets(file, Some(name), Some(10), None).unwrap();
 /// assert_eq!(streets.len(), 2);
 /// ```
 pub fn streets(
     file: impl Seek + Read,
     name: Option<&str>,
     boundary: Option<u8>,
+    node_store: Option<&Path>,
 ) -> Result<Vec<Street>, Box<dyn Error>> {
     let mut pbf = OsmPbfReader::new(file);
     let groups = build_street_group(name);
-    let objs = pbf.get_objs_and_deps(|obj| obj.filter(&groups))?;
-    let streets = extract_streets(&objs);
+    let streets = match node_store {
+        Some(path) => {
+            let mut store = LocationStore::create(path)?;
+            let mut objs = BTreeMap::new();
+            for obj in pbf.par_iter() {
+                match obj? {
+                    OsmObj::Node(node) => store.insert(&node)?,
+                    obj if obj.is_way() && obj.filter(&groups) => {
+                        objs.insert(obj.id(), obj);
+                    }
+                    _ => {}
+                }
+            }
+            extract_streets(&objs, &store)
+        }
+        None => {
+            let objs = pbf.get_objs_and_deps(|obj| obj.filter(&groups))?;
+            extract_streets(&objs, &objs)
+        }
+    };
     let streets = {
         match boundary {
             None => streets,
@@ -183,68 +305,594 @@ pub fn streets(
     Ok(streets)
 }
 
+/// Options of an extraction with `objects` or `stream_objects`
+#[derive(Default)]
+pub struct ObjectOptions<'a> {
+    /// Filter groups selecting the objects, all objects are extracted if none are given
+    pub groups: Option<&'a [Group]>,
+    /// Include the coordinates of ways and relations
+    pub retain_coordinates: bool,
+    /// Restrict the output to objects related to the area by the predicate
+    pub area: Option<(&'a Area, SpatialPredicate)>,
+    /// List the groups each object matched
+    pub report_groups: bool,
+    /// Labels of the groups by position, groups without one are reported by their index
+    pub labels: &'a [Option<String>],
+    /// Include the node ids of ways and the members of relations
+    pub retain_refs: bool,
+    /// Include the area or length of ways and multipolygons
+    pub measures: bool,
+    /// Include a point on the surface of closed ways and multipolygons
+    pub point_on_surface: bool,
+    /// Tags to keep, drop or rename in the output
+    pub tags: TagProjection,
+    /// File to hold the locations of all nodes, instead of keeping them in memory, which must
+    /// not exist yet
+    pub node_store: Option<&'a Path>,
+}
+
 /// Extract Objects from OSM
 ///
 /// Objects (i.e. Nodes, Ways & Relations) will be extracted according to filter options. Some geographic properties (centroid, bounding boxes) are computed for all entities.
 ///
-/// Filtering `groups` can be applied to select objects according to their tags.
+/// The geometry of `type=multipolygon` relations is assembled from the outer and inner rings of
+/// their members, other relations are represented by the convex hull of their members.
+///
+/// Filtering `groups` can be applied to select objects according to their tags, or by id using
+/// `Condition::Ids`. If the groups contain membership conditions, the relations of the file are
+/// indexed in an additional pass. Geometry conditions are evaluated once an object's
+/// coordinates have been resolved.
+///
+/// If every group is restricted to a list of ids, a pass over the file stops as soon as all
+/// elements it looks for have been found. Ids missing from the file cause it to be read
+/// completely.
+///
+/// With `report_groups`, each object lists the groups it matched, by their entry in `labels`
+/// or else by index. Labels are kept by `filter::parse_labeled` and `Profiles::groups`.
+///
+/// With `retain_refs`, ways list the ids of their nodes and relations their members, with
+/// element type, id and role.
+///
+/// With `measures`, closed ways and multipolygons carry their area in m² and open ways their
+/// length in m. Both are a spherical approximation, not geodesic measures on the ellipsoid.
+///
+/// With `point_on_surface`, closed ways and multipolygons carry a point which is guaranteed to
+/// lie inside of them, unlike their centroid.
+///
+/// The `tags` projection selects and renames the tags written for each object. It does not
+/// affect filtering, which always considers all tags.
+///
+/// An `area` restricts the output to objects which intersect or lie within the given bounds,
+/// administrative boundary or polygon. With `SpatialPredicate::Centroid` only an object's
+/// location or centroid is considered. If several boundaries share a name, the one with the
+/// lowest `admin_level` is used.
+///
+/// Node coordinates are held in memory by default. For inputs too large for that, a
+/// `node_store` path can be given, where the locations of all nodes are written to a file
+/// mapped into memory in the first pass over the input. It is an error if the file already
+/// exists, and it is removed afterwards.
 ///
 /// # Example
 ///
 /// ```
 /// use std::fs::File;
-/// use osm_pbf2json::objects;
+/// use osm_pbf2json::{objects, ObjectOptions};
 /// use osm_pbf2json::filter::{Condition, Group};
 ///
 /// let file = File::open("./tests/data/alexanderplatz.pbf").unwrap();
 /// let cond_1 = Condition::new("surface", Some("cobblestone"));
 /// let cond_2 = Condition::new("highway", None);
-/// let group = Group { conditions: vec![cond_1, cond_2] };
-/// let cobblestone_ways = objects(file, Some(&vec![group]), false).unwrap();
+/// let groups = vec![Group { conditions: vec![cond_1, cond_2] }];
+/// let options = ObjectOptions {
+///     groups: Some(&groups),
+///     ..Default::default()
+/// };
+/// let cobblestone_ways = objects(file, &options).unwrap();
 /// assert_eq!(cobblestone_ways.len(), 4);
 /// ```
 pub fn objects(
     file: impl Seek + Read,
-    groups: Option<&[Group]>,
-    retain_coordinates: bool,
+    options: &ObjectOptions,
 ) -> Result<Vec<osm::Object>, Box<dyn Error>> {
+    let mut objects = vec![];
+    let sink = |object| {
+        objects.push(object);
+        Ok(())
+    };
+    stream_objects(file, options, sink)?;
+    Ok(objects)
+}
+
+/// Extract Objects from OSM, passing each one to `sink` as soon as its geometry is resolved
+///
+/// Takes the same options as `objects`. Only the elements matching objects depend on are kept
+/// in memory (without the tags of nodes and ways), not the extracted objects themselves. Nodes
+/// and ways are passed on while the file is read, so it needs to list nodes before ways, as is
+/// conventional. Relations are passed on after the file has been read.
+pub fn stream_objects(
+    file: impl Seek + Read,
+    options: &ObjectOptions,
+    mut sink: impl FnMut(osm::Object) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let groups = options.groups;
     let mut pbf = OsmPbfReader::new(file);
 
-    let objs = match groups {
-        Some(grps) => pbf.get_objs_and_deps(|obj| obj.filter(grps))?,
-        None => pbf.get_objs_and_deps(|_| true)?,
+    let boundary_area;
+    let area = match options.area {
+        Some((Area::Boundary(name), predicate)) => {
+            let groups = build_boundary_group(name);
+            let objs = pbf.get_objs_and_deps(|obj| obj.filter(&groups))?;
+            let boundary = get_boundaries(&objs)
+                .into_iter()
+                .min_by_key(|boundary| boundary.admin_level)
+                .ok_or_else(|| format!("administrative boundary '{}' not found", name))?;
+            boundary_area = Area::Polygon(boundary.geometry);
+            Some((&boundary_area, predicate))
+        }
+        area => area,
+    };
+
+    let index = match groups {
+        Some(grps) if grps.iter().any(Group::uses_membership) => {
+            pbf.rewind()?;
+            let mut relations = vec![];
+            for obj in pbf.par_iter() {
+                if let OsmObj::Relation(relation) = obj? {
+                    relations.push(relation);
+                }
+            }
+            MemberIndex::new(relations)
+        }
+        _ => MemberIndex::default(),
+    };
+
+    let extraction = Extraction {
+        labels: options.labels,
+        filter: groups.map(CompiledFilter::new),
+        ids: groups.and_then(requested_ids),
+        index,
+        retain_coordinates: options.retain_coordinates,
+        area,
+        report_groups: options.report_groups,
+        retain_refs: options.retain_refs,
+        measures: options.measures,
+        point_on_surface: options.point_on_surface,
+        tags: &options.tags,
+    };
+
+    let mut store = options.node_store.map(LocationStore::create).transpose()?;
+    let (deps, matches) = collect_dependencies(&mut pbf, &extraction, store.as_mut())?;
+
+    // ways and relations were matched in the first pass, only nodes are matched here
+    pbf.rewind()?;
+    let mut objs = BTreeMap::new();
+    let mut relations = vec![];
+    let mut pending: Option<BTreeSet<OsmId>> = extraction
+        .ids
+        .as_ref()
+        .map(|ids| ids.union(&deps).copied().collect());
+    for obj in pbf.par_iter() {
+        if pending.as_ref().is_some_and(BTreeSet::is_empty) {
+            break;
+        }
+        let obj = obj?;
+        if let Some(pending) = pending.as_mut() {
+            pending.remove(&obj.id());
+        }
+        if deps.contains(&obj.id()) {
+            objs.insert(obj.id(), without_tags(&obj));
+        }
+        let indices = if obj.is_node() {
+            extraction.matching_groups(&obj)
+        } else {
+            matches.get(obj.id())
+        };
+        let indices = match indices {
+            Some(indices) => indices,
+            None => continue,
+        };
+        let locations: &dyn NodeLocations = match &store {
+            Some(store) => store,
+            None => &objs,
+        };
+        if obj.is_relation() {
+            relations.push((obj, indices));
+        } else if let Some(object) = extraction.build_object(&obj, indices, &objs, locations) {
+            sink(object)?;
+        }
+    }
+    let locations: &dyn NodeLocations = match &store {
+        Some(store) => store,
+        None => &objs,
+    };
+    for (obj, indices) in relations {
+        if let Some(object) = extraction.build_object(&obj, indices, &objs, locations) {
+            sink(object)?;
+        }
+    }
+    Ok(())
+}
+
+/// Extract addresses from OSM
+///
+/// Nodes, ways and relations with an `addr:housenumber` or `addr:housename` tag are extracted
+/// as addresses, with their street or place, postcode and city. Several house numbers can be
+/// given separated by semicolons. Ways and relations are located by a point on their surface,
+/// or else their centroid.
+///
+/// An `area` and a `node_store` are applied as for `objects`.
+///
+/// # Example
+///
+/// ```
+/// use std::fs::File;
+/// use osm_pbf2json::addresses;
+///
+/// let file = File::open("./tests/data/alexanderplatz.pbf").unwrap();
+/// let addresses = addresses(file, None, None).unwrap();
+/// assert_eq!(addresses.len(), 712);
+/// ```
+pub fn addresses(
+    file: impl Seek + Read,
+    area: Option<(&Area, SpatialPredicate)>,
+    node_store: Option<&Path>,
+) -> Result<Vec<Address>, Box<dyn Error>> {
+    let groups = build_address_groups();
+    // shapes are located by a point on their surface
+    let options = ObjectOptions {
+        groups: Some(&groups),
+        area,
+        point_on_surface: true,
+        node_store,
+        ..Default::default()
+    };
+    let mut addresses = vec![];
+    stream_objects(file, &options, |object| {
+        addresses.extend(extract_address(&object));
+        Ok(())
+    })?;
+    Ok(addresses)
+}
+
+/// Tags are only needed to tell multipolygons apart, so they are dropped from other elements
+/// which are kept to resolve geometries
+fn without_tags(obj: &OsmObj) -> OsmObj {
+    match obj {
+        OsmObj::Node(node) => OsmObj::Node(Node {
+            tags: Tags::new(),
+            ..node.clone()
+        }),
+        OsmObj::Way(way) => OsmObj::Way(Way {
+            tags: Tags::new(),
+            ..way.clone()
+        }),
+        OsmObj::Relation(_) => obj.clone(),
+    }
+}
+
+/// Ways and relations matched in the first pass, only as much is kept as the output needs
+enum Matches {
+    /// Without a filter every element matches
+    All,
+    Ids(BTreeSet<OsmId>),
+    /// Indices of the matching groups by id, if they are reported or have geometry conditions
+    Groups(BTreeMap<OsmId, Vec<usize>>),
+}
+
+impl Matches {
+    fn new(extraction: &Extraction) -> Self {
+        match &extraction.filter {
+            None => Matches::All,
+            Some(_) if extraction.keeps_indices() => Matches::Groups(BTreeMap::new()),
+            Some(_) => Matches::Ids(BTreeSet::new()),
+        }
+    }
+
+    fn insert(&mut self, id: OsmId, indices: Vec<usize>) {
+        match self {
+            Matches::All => {}
+            Matches::Ids(ids) => {
+                ids.insert(id);
+            }
+            Matches::Groups(groups) => {
+                groups.insert(id, indices);
+            }
+        }
+    }
+
+    fn contains(&self, id: &OsmId) -> bool {
+        match self {
+            Matches::All => true,
+            Matches::Ids(ids) => ids.contains(id),
+            Matches::Groups(groups) => groups.contains_key(id),
+        }
+    }
+
+    /// Indices of the groups the element matched (if they are kept), `None` if it did not match
+    fn get(&self, id: OsmId) -> Option<Vec<usize>> {
+        match self {
+            Matches::All => Some(vec![]),
+            Matches::Ids(ids) => ids.contains(&id).then(Vec::new),
+            Matches::Groups(groups) => groups.get(&id).cloned(),
+        }
+    }
+}
+
+/// Ids of all elements the geometries of matching objects depend on, member relations are
+/// resolved in additional passes until no new dependencies are found
+///
+/// The ways and relations which matched are returned as well, so the filter is evaluated only
+/// once per element.
+///
+/// With a `store`, the locations of all nodes are written to it in the first pass instead of
+/// nodes being collected as dependencies.
+fn collect_dependencies(
+    pbf: &mut OsmPbfReader<impl Seek + Read>,
+    extraction: &Extraction,
+    mut store: Option<&mut LocationStore>,
+) -> Result<(BTreeSet<OsmId>, Matches), Box<dyn Error>> {
+    let mut deps = BTreeSet::new();
+    let mut matches = Matches::new(extraction);
+    let mut resolved: BTreeSet<OsmId> = BTreeSet::new();
+    let with_nodes = store.is_none();
+    let add_refs = |obj: &OsmObj, deps: &mut BTreeSet<OsmId>| match obj {
+        OsmObj::Node(_) => {}
+        OsmObj::Way(_) if !with_nodes => {}
+        OsmObj::Way(way) => deps.extend(way.nodes.iter().map(|&id| OsmId::from(id))),
+        OsmObj::Relation(rel) => deps.extend(
+            rel.refs
+                .iter()
+                .map(|r| r.member)
+                .filter(|id| with_nodes || !id.is_node()),
+        ),
     };
 
-    let objects = objs
-        .values()
-        .filter_map(|obj| {
-            if groups.is_some() && !obj.filter(groups?) {
+    // with explicit ids and without a store, reading stops once all requested ways and
+    // relations have been found
+    let mut pending: Option<BTreeSet<OsmId>> = match &extraction.ids {
+        Some(ids) if with_nodes => Some(ids.iter().filter(|id| !id.is_node()).copied().collect()),
+        _ => None,
+    };
+    pbf.rewind()?;
+    for obj in pbf.par_iter() {
+        if pending.as_ref().is_some_and(BTreeSet::is_empty) {
+            break;
+        }
+        let obj = obj?;
+        if let Some(pending) = pending.as_mut() {
+            pending.remove(&obj.id());
+        }
+        if let (Some(store), OsmObj::Node(node)) = (store.as_mut(), &obj) {
+            store.insert(node)?;
+        }
+        if obj.is_node() {
+            continue;
+        }
+        if let Some(indices) = extraction.matching_groups(&obj) {
+            add_refs(&obj, &mut deps);
+            matches.insert(obj.id(), indices);
+        }
+    }
+
+    loop {
+        let unresolved: BTreeSet<OsmId> = deps
+            .iter()
+            .filter(|id| !id.is_node() && !matches.contains(id) && !resolved.contains(id))
+            .copied()
+            .collect();
+        if unresolved.is_empty() {
+            return Ok((deps, matches));
+        }
+        pbf.rewind()?;
+        let mut pending = unresolved.clone();
+        for obj in pbf.par_iter() {
+            if pending.is_empty() {
+                break;
+            }
+            let obj = obj?;
+            if pending.remove(&obj.id()) {
+                add_refs(&obj, &mut deps);
+            }
+        }
+        // members missing from the file can not be resolved either
+        resolved.extend(unresolved);
+    }
+}
+
+/// Ids all matching elements are among, if each group is restricted to a list of ids
+fn requested_ids(groups: &[Group]) -> Option<BTreeSet<OsmId>> {
+    let mut requested = BTreeSet::new();
+    for group in groups {
+        let ids = group
+            .conditions
+            .iter()
+            .find_map(|condition| match condition {
+                Condition::Ids(ids) => Some(ids),
+                _ => None,
+            })?;
+        requested.extend(ids.iter().copied());
+    }
+    Some(requested)
+}
+
+/// Options of an extraction, applied to the objects as they are read
+struct Extraction<'a> {
+    labels: &'a [Option<String>],
+    filter: Option<CompiledFilter>,
+    ids: Option<BTreeSet<OsmId>>,
+    index: MemberIndex,
+    retain_coordinates: bool,
+    area: Option<(&'a Area, SpatialPredicate)>,
+    report_groups: bool,
+    retain_refs: bool,
+    measures: bool,
+    point_on_surface: bool,
+    tags: &'a TagProjection,
+}
+
+impl Extraction<'_> {
+    /// Whether the indices of matching groups are needed after the first evaluation, to report
+    /// them or to check their geometry conditions
+    fn keeps_indices(&self) -> bool {
+        self.report_groups
+            || self
+                .filter
+                .as_ref()
+                .is_some_and(CompiledFilter::uses_geometry)
+    }
+
+    /// Indices of the groups matching the element (if they are kept), `None` if it does not
+    /// match. Geometry conditions are assumed to hold.
+    fn matching_groups(&self, obj: &OsmObj) -> Option<Vec<usize>> {
+        let filter = match &self.filter {
+            Some(filter) => filter,
+            None => return Some(vec![]),
+        };
+        if let Some(ids) = &self.ids {
+            if !ids.contains(&obj.id()) {
+                return None;
+            }
+        }
+        if self.keeps_indices() {
+            let indices = filter.matching_groups(obj, &self.index);
+            if indices.is_empty() {
+                None
+            } else {
+                Some(indices)
+            }
+        } else {
+            filter.matches(obj, &self.index).then(Vec::new)
+        }
+    }
+
+    fn build_object(
+        &self,
+        obj: &OsmObj,
+        mut indices: Vec<usize>,
+        objs: &BTreeMap<OsmId, OsmObj>,
+        locations: &dyn NodeLocations,
+    ) -> Option<osm::Object> {
+        // the geometry of relations other than multipolygons is approximated for computations,
+        // while the geometries of their members are kept for the output
+        let (geo_info, geometry, members) = match obj {
+            OsmObj::Node(obj) => {
+                let geo_info = osm::GeoInfo::Point {
+                    lon: obj.lon(),
+                    lat: obj.lat(),
+                };
+                let point = Point::new(obj.lon(), obj.lat());
+                (geo_info, Some(Geometry::Point(point)), None)
+            }
+            OsmObj::Way(obj) => {
+                let coordinates = obj.get_coordinates(locations);
+                let geometry = get_geometry(&coordinates);
+                let geo_info = osm::GeoInfo::new_shape(
+                    geometry.as_ref(),
+                    &coordinates,
+                    self.retain_coordinates,
+                );
+                (geo_info, geometry, None)
+            }
+            OsmObj::Relation(obj) => {
+                let coordinates = obj.get_coordinates(objs, locations, &mut vec![]);
+                let (geometry, members) = match get_multi_polygon(obj, objs, locations) {
+                    Some(multi_polygon) => (Some(Geometry::MultiPolygon(multi_polygon)), None),
+                    None => {
+                        let members = get_member_geometries(obj, objs, locations, &mut vec![]);
+                        let collection = GeometryCollection(members);
+                        let members = Some(Geometry::GeometryCollection(collection));
+                        (get_geometry(&coordinates), members)
+                    }
+                };
+                let geo_info = osm::GeoInfo::new_shape(
+                    geometry.as_ref(),
+                    &coordinates,
+                    self.retain_coordinates,
+                );
+                (geo_info, geometry, members)
+            }
+        };
+
+        let geometry_filter = self.filter.as_ref().filter(|filter| filter.uses_geometry());
+        // the convex hull of other relations is too rough an approximation to be measured
+        let measured = members.is_none() && (geometry_filter.is_some() || self.measures);
+        let (area, length) = if measured {
+            geometry.as_ref().map_or((None, None), get_measures)
+        } else {
+            (None, None)
+        };
+
+        // only the geometry conditions of the groups which matched are left to evaluate
+        if let Some(filter) = geometry_filter {
+            let measures = Measures { area, length };
+            indices.retain(|&i| filter.matches_with_measures(i, obj, &self.index, &measures));
+            if indices.is_empty() {
                 return None;
             }
+        }
 
-            let object = match obj {
-                OsmObj::Node(obj) => {
-                    let geo_info = osm::GeoInfo::Point {
-                        lon: obj.lon(),
-                        lat: obj.lat(),
-                    };
-                    osm::Object::new(obj.id.0, "node", obj.tags.clone(), geo_info)
+        if let Some((area, predicate)) = self.area {
+            if !area.matches(predicate, &geo_info, geometry.as_ref()) {
+                return None;
+            }
+        }
+
+        let geo_info = if self.measures && members.is_none() {
+            geo_info.with_measures(area, length)
+        } else {
+            geo_info
+        };
+        // a point inside the convex hull need not lie on any of the members
+        let geo_info = if self.point_on_surface && members.is_none() {
+            geo_info.with_point_on_surface(geometry.as_ref())
+        } else {
+            geo_info
+        };
+
+        let (id, osm_type) = match obj {
+            OsmObj::Node(obj) => (obj.id.0, "node"),
+            OsmObj::Way(obj) => (obj.id.0, "way"),
+            OsmObj::Relation(obj) => (obj.id.0, "relation"),
+        };
+        let matched_groups = if self.filter.is_some() && self.report_groups {
+            // several groups can share a label, e.g. those of a profile
+            let mut refs = vec![];
+            for i in indices {
+                let group_ref = match self.labels.get(i) {
+                    Some(Some(label)) => osm::GroupRef::Label(label.clone()),
+                    _ => osm::GroupRef::Index(i),
+                };
+                if !refs.contains(&group_ref) {
+                    refs.push(group_ref);
                 }
+            }
+            Some(refs)
+        } else {
+            None
+        };
+        let tags = if self.tags.is_identity() {
+            obj.tags().clone()
+        } else {
+            self.tags.apply(obj.tags())
+        };
+        let mut object = osm::Object::new(id, osm_type, tags, geo_info)
+            .with_groups(matched_groups)
+            .with_geometry(members.or(geometry));
+        if self.retain_refs {
+            object = match obj {
+                OsmObj::Node(_) => object,
                 OsmObj::Way(obj) => {
-                    let coordinates = obj.get_coordinates(&objs);
-                    let geo_info = osm::GeoInfo::new_shape(&coordinates, retain_coordinates);
-                    osm::Object::new(obj.id.0, "way", obj.tags.clone(), geo_info)
+                    let nodes = obj.nodes.iter().map(|id| id.0).collect();
+                    object.with_nodes(Some(nodes))
                 }
                 OsmObj::Relation(obj) => {
-                    let coordinates = obj.get_coordinates(&objs, &mut vec![]);
-                    let geo_info = osm::GeoInfo::new_shape(&coordinates, retain_coordinates);
-                    osm::Object::new(obj.id.0, "relation", obj.tags.clone(), geo_info)
+                    let members = obj.refs.iter().map(get_member).collect();
+                    object.with_members(Some(members))
                 }
             };
-            Some(object)
-        })
-        .collect();
-    Ok(objects)
+        }
+        Some(object)
+    }
 }
 
 #[cfg(test)]
@@ -302,7 +950,7 @@ mod get_coordinates {
         let obj_map = BTreeMap::new();
         let id = RelationId(42);
         let rel = create_relation(id, vec![]);
-        let coordinates = rel.get_coordinates(&obj_map, &mut vec![]);
+        let coordinates = rel.get_coordinates(&obj_map, &obj_map, &mut vec![]);
         assert_eq!(coordinates.len(), 0);
     }
 
@@ -329,7 +977,7 @@ mod get_coordinates {
 
         // we expect a closed triangle
 
-        let coordinates = rel.get_coordinates(&obj_map, &mut vec![]);
+        let coordinates = rel.get_coordinates(&obj_map, &obj_map, &mut vec![]);
         assert_eq!(
             coordinates,
             vec![(9., 50.), (9., 51.), (10., 51.), (9., 50.)]
@@ -345,7 +993,7 @@ mod get_coordinates {
         let id = RelationId(42);
         let refs = create_refs(vec![node_id.into()]);
         let rel = create_relation(id, refs);
-        let coordinates = rel.get_coordinates(&obj_map, &mut vec![]);
+        let coordinates = rel.get_coordinates(&obj_map, &obj_map, &mut vec![]);
         assert_eq!(coordinates, vec![(5., 49.)]);
     }
 
@@ -369,7 +1017,7 @@ mod get_coordinates {
         let id = RelationId(42);
         let refs = create_refs(node_ids.into_iter().map(NodeId::into).collect());
         let rel = create_relation(id, refs);
-        let coordinates = rel.get_coordinates(&obj_map, &mut vec![]);
+        let coordinates = rel.get_coordinates(&obj_map, &obj_map, &mut vec![]);
 
         // We expect a simplified closed rectangle.
         //
@@ -405,7 +1053,7 @@ mod get_coordinates {
         let refs = create_refs(vec![child_id.into(), node_id.into()]);
         let parent_rel = create_relation(parent_id, refs);
 
-        let coordinates = parent_rel.get_coordinates(&obj_map, &mut vec![]);
+        let coordinates = parent_rel.get_coordinates(&obj_map, &obj_map, &mut vec![]);
 
         assert_eq!(
             coordinates,
@@ -429,8 +1077,54 @@ mod get_coordinates {
         let refs = create_refs(vec![rel_id_1.into(), node_id.into()]);
         let rel_2 = create_relation(rel_id_2, refs);
 
-        let coordinates = rel_2.get_coordinates(&obj_map, &mut vec![]);
+        let coordinates = rel_2.get_coordinates(&obj_map, &obj_map, &mut vec![]);
 
         assert_eq!(coordinates, vec![(8., 52.)]);
     }
+
+    #[test]
+    fn multipolygon_relation() {
+        let mut obj_map = BTreeMap::new();
+        let mut add_way = |id: i64, coordinates: Vec<(i32, i32)>| {
+            let node_ids = coordinates
+                .into_iter()
+                .map(|(lng, lat)| {
+                    let node_id = NodeId(i64::from(lng * 100 + lat));
+                    obj_map.insert(node_id.into(), create_node(node_id, lng, lat).into());
+                    node_id
+                })
+                .collect();
+            let way_id = WayId(id);
+            obj_map.insert(way_id.into(), create_way(way_id, node_ids).into());
+            Ref {
+                member: way_id.into(),
+                role: if id == 3 { "inner" } else { "outer" }.into(),
+            }
+        };
+
+        // the first outer ring is split into two ways, the second one lies apart
+        let refs = vec![
+            add_way(1, vec![(0, 0), (4, 0), (4, 4)]),
+            add_way(2, vec![(4, 4), (0, 4), (0, 0)]),
+            add_way(3, vec![(1, 1), (2, 1), (2, 2), (1, 2), (1, 1)]),
+            add_way(4, vec![(10, 0), (11, 0), (11, 1), (10, 1), (10, 0)]),
+        ];
+        let mut rel = create_relation(RelationId(42), refs);
+        assert_eq!(get_multi_polygon(&rel, &obj_map, &obj_map), None);
+
+        rel.tags.insert("type".into(), "multipolygon".into());
+        let multi_polygon = get_multi_polygon(&rel, &obj_map, &obj_map).unwrap();
+        assert_eq!(multi_polygon.0.len(), 2);
+        let interiors: Vec<usize> = multi_polygon
+            .0
+            .iter()
+            .map(|polygon| polygon.interiors().len())
+            .collect();
+        assert_eq!(interiors, vec![1, 0]);
+
+        // nodes kept with the ways are used without looking up their locations
+        let no_locations = BTreeMap::new();
+        let from_objs = get_multi_polygon(&rel, &obj_map, &no_locations);
+        assert_eq!(from_objs, Some(multi_polygon));
+    }
 }
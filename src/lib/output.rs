@@ -1,7 +1,7 @@
 use super::geo::Length;
 use super::geojson::{Entity, Geometry};
 use super::items::osm::Object;
-use super::items::{AdminBoundary, Street};
+use super::items::{Address, AdminBoundary, Street};
 use rand::random;
 use serde::{Deserialize, Serialize};
 use serde_json::to_string;
@@ -61,7 +61,7 @@ impl Output for Vec<AdminBoundary> {
                 .into_iter()
                 .collect();
                 Entity::Feature {
-                    geometry,
+                    geometry: Some(geometry),
                     properties,
                 }
             })
@@ -83,6 +83,23 @@ struct JSONStreet {
     loc: (f64, f64),
 }
 
+/// Objects without a geometry, e.g. relations without any members in the extract, are written
+/// as features with a `null` geometry
+fn object_feature(object: &Object) -> Entity {
+    let geometry = object.geometry().map(Geometry::from);
+    let mut properties: HashMap<String, String> = object
+        .tags()
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    properties.insert("@id".into(), object.id().to_string());
+    properties.insert("@type".into(), object.osm_type().into());
+    Entity::Feature {
+        geometry,
+        properties,
+    }
+}
+
 impl Output for Vec<Object> {
     fn write_json_lines(&self, writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
         for object in self.iter() {
@@ -92,8 +109,107 @@ impl Output for Vec<Object> {
         Ok(())
     }
 
-    fn write_geojson(&self, _writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
-        unimplemented!();
+    fn write_geojson(&self, writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+        let features = self.iter().map(object_feature).collect();
+        let feature_collection = Entity::FeatureCollection { features };
+        let string = to_string(&feature_collection)?;
+        writeln!(writer, "{}", string)?;
+        Ok(())
+    }
+}
+
+impl Output for Vec<Address> {
+    fn write_json_lines(&self, writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+        for address in self.iter() {
+            let json = to_string(address)?;
+            writeln!(writer, "{}", json)?;
+        }
+        Ok(())
+    }
+
+    fn write_geojson(&self, writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+        let features = self
+            .iter()
+            .filter_map(|address| {
+                let location = address.location.as_ref()?;
+                let geometry = Geometry::Point {
+                    coordinates: (location.lon, location.lat),
+                };
+                let housenumbers = Some(address.housenumbers.join(";"));
+                let fields = vec![
+                    ("street", &address.street),
+                    ("place", &address.place),
+                    ("housenumbers", &housenumbers),
+                    ("housename", &address.housename),
+                    ("postcode", &address.postcode),
+                    ("city", &address.city),
+                ];
+                let mut properties: HashMap<String, String> = fields
+                    .into_iter()
+                    .filter_map(|(key, value)| Some((key.to_string(), value.clone()?)))
+                    .filter(|(_, value)| !value.is_empty())
+                    .collect();
+                properties.insert("@id".into(), address.id.to_string());
+                properties.insert("@type".into(), address.osm_type.into());
+                Some(Entity::Feature {
+                    geometry: Some(geometry),
+                    properties,
+                })
+            })
+            .collect();
+        let feature_collection = Entity::FeatureCollection { features };
+        let string = to_string(&feature_collection)?;
+        writeln!(writer, "{}", string)?;
+        Ok(())
+    }
+}
+
+/// Writes objects one at a time, in the same formats as `Output` for `Vec<Object>`
+///
+/// GeoJSON features are written into a FeatureCollection, which is closed by `finish`.
+pub struct ObjectWriter<'a> {
+    writer: &'a mut dyn Write,
+    geojson: bool,
+    features: usize,
+}
+
+impl<'a> ObjectWriter<'a> {
+    pub fn new(writer: &'a mut dyn Write, geojson: bool) -> Self {
+        ObjectWriter {
+            writer,
+            geojson,
+            features: 0,
+        }
+    }
+
+    pub fn write(&mut self, object: &Object) -> Result<(), Box<dyn Error>> {
+        if !self.geojson {
+            let json = to_string(object)?;
+            writeln!(self.writer, "{}", json)?;
+            return Ok(());
+        }
+        let feature = object_feature(object);
+        let separator = if self.features == 0 {
+            r#"{"type":"FeatureCollection","features":["#
+        } else {
+            ","
+        };
+        write!(self.writer, "{}{}", separator, to_string(&feature)?)?;
+        self.features += 1;
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), Box<dyn Error>> {
+        if self.geojson {
+            match self.features {
+                0 => writeln!(
+                    self.writer,
+                    r#"{{"type":"FeatureCollection","features":[]}}"#
+                )?,
+                _ => writeln!(self.writer, "]}}")?,
+            }
+        }
+        Ok(())
     }
 }
 
@@ -144,7 +260,7 @@ impl Output for Vec<Street> {
                     properties.insert("boundary".into(), name.clone());
                 }
                 let entity = Entity::Feature {
-                    geometry,
+                    geometry: Some(geometry),
                     properties,
                 };
                 Some(entity)
@@ -1,6 +1,7 @@
 use super::geo::{Length, Midpoint, SegmentGeometry};
 use super::items::AdminBoundary;
 use super::items::{Segment, Street};
+use super::locations::NodeLocations;
 use itertools::Itertools;
 use osmpbfreader::objects::{OsmId, OsmObj, Way};
 use petgraph::algo::kosaraju_scc;
@@ -102,23 +103,18 @@ impl Street {
     }
 }
 
-fn get_coordinates(way: &Way, objs: &BTreeMap<OsmId, OsmObj>) -> Option<Vec<(f64, f64)>> {
+fn get_coordinates(way: &Way, locations: &dyn NodeLocations) -> Option<Vec<(f64, f64)>> {
     let coordinates = way
         .nodes
         .iter()
-        .filter_map(|&node_id| {
-            let obj = objs.get(&node_id.into())?;
-            let node = obj.node()?;
-            let coordinate = (node.lon(), node.lat());
-            Some(coordinate)
-        })
+        .filter_map(|&node_id| locations.location(node_id))
         .collect();
     Some(coordinates)
 }
 
-fn get_segments(ways: &[&Way], objs: &BTreeMap<OsmId, OsmObj>) -> Vec<Segment> {
+fn get_segments(ways: &[&Way], locations: &dyn NodeLocations) -> Vec<Segment> {
     ways.iter()
-        .filter_map(|way| Segment::new(way, objs).ok())
+        .filter_map(|way| Segment::new(way, locations).ok())
         .collect()
 }
 
@@ -173,11 +169,15 @@ fn get_name_groups(objs: &BTreeMap<OsmId, OsmObj>) -> HashMap<&str, Vec<&Way>> {
         .into_group_map()
 }
 
-pub fn extract_streets(objs: &BTreeMap<OsmId, OsmObj>) -> Vec<Street> {
+/// Streets from the named ways among `objs`, whose nodes are looked up in `locations`
+pub fn extract_streets(
+    objs: &BTreeMap<OsmId, OsmObj>,
+    locations: &(dyn NodeLocations + Sync),
+) -> Vec<Street> {
     get_name_groups(objs)
         .into_par_iter()
         .flat_map(|(name, ways)| {
-            let segments = get_segments(&ways, objs);
+            let segments = get_segments(&ways, locations);
             let clusters = get_clusters(segments);
             let streets: Vec<Street> = clusters
                 .iter()
@@ -234,10 +234,10 @@ impl PartialEq for Segment {
 impl Eq for Segment {}
 
 impl Segment {
-    fn new(way: &Way, objs: &BTreeMap<OsmId, OsmObj>) -> Result<Self, &'static str> {
+    fn new(way: &Way, locations: &dyn NodeLocations) -> Result<Self, &'static str> {
         let way_id = way.id.0;
         let coordinates =
-            get_coordinates(way, objs).ok_or("could not construct coordinates for way")?;
+            get_coordinates(way, locations).ok_or("could not construct coordinates for way")?;
         let geometry = SegmentGeometry::new(coordinates)?;
         let segment = Segment { way_id, geometry };
         Ok(segment)
@@ -297,7 +297,7 @@ mod tests {
         let node_ids = vec![NodeId(3), NodeId(4)];
         add_way(WayId(43), "street a", node_ids, &mut objs);
 
-        let streets = extract_streets(&objs);
+        let streets = extract_streets(&objs, &objs);
         assert_eq!(streets.len(), 1);
 
         let street = &streets[0];
@@ -325,7 +325,7 @@ mod tests {
         let node_ids = vec![NodeId(2), NodeId(3)];
         add_way(WayId(41), "street b", node_ids, &mut objs);
 
-        let streets = extract_streets(&objs);
+        let streets = extract_streets(&objs, &objs);
         assert_eq!(streets.len(), 2);
     }
 
@@ -343,7 +343,7 @@ mod tests {
         let node_ids = vec![NodeId(2), NodeId(3)];
         add_way(WayId(41), "street b", node_ids, &mut objs);
 
-        let streets = extract_streets(&objs);
+        let streets = extract_streets(&objs, &objs);
         assert_eq!(streets.len(), 2);
     }
 
@@ -1,5 +1,5 @@
 use osm_boundaries_utils::osm_builder::{named_node, OsmBuilder};
-use osmpbfreader::objects::{OsmId, OsmObj, Relation};
+use osmpbfreader::objects::{OsmId, OsmObj, Relation, Tags};
 use std::collections::BTreeMap;
 
 trait OsmObjExt {
@@ -16,6 +16,15 @@ impl OsmObjExt for OsmObj {
     }
 }
 
+#[allow(dead_code)]
+pub fn create_tags(pairs: &[(&str, &str)]) -> Tags {
+    let mut tags = Tags::new();
+    for (key, value) in pairs {
+        tags.insert((*key).into(), (*value).into());
+    }
+    tags
+}
+
 #[allow(dead_code)]
 pub fn create_objects(tags: &[(&str, &str)], coordinates: &[[f64; 2]]) -> BTreeMap<OsmId, OsmObj> {
     let mut nodes: Vec<_> = coordinates
@@ -1,12 +1,12 @@
-use lib::output::Output;
-use lib::{boundaries, filter, objects, streets};
+use osm_pbf2json::filter::{Condition, Group, LabeledGroup, Profiles, TagProjection};
+use osm_pbf2json::items::osm::{Area, Bounds, SpatialPredicate};
+use osm_pbf2json::output::{ObjectWriter, Output};
+use osm_pbf2json::{addresses, boundaries, filter, stream_objects, streets, ObjectOptions};
 use std::error::Error;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io;
 use structopt::StructOpt;
 
-mod lib;
-
 #[derive(StructOpt)]
 struct Cli {
     #[structopt(parse(from_os_str))]
@@ -16,12 +16,51 @@ struct Cli {
 }
 
 #[derive(StructOpt)]
+#[allow(clippy::large_enum_variant)]
 enum Command {
     Objects {
         #[structopt(short, long)]
         tags: Option<String>,
+        #[structopt(long, parse(from_os_str))]
+        filter_file: Option<std::path::PathBuf>,
+        #[structopt(long, requires = "filter-file")]
+        profile: Vec<String>,
         #[structopt(short, long)]
         retain_coordinates: bool,
+        #[structopt(long)]
+        normalize_values: bool,
+        #[structopt(long)]
+        report_groups: bool,
+        #[structopt(long)]
+        retain_refs: bool,
+        #[structopt(long)]
+        measures: bool,
+        #[structopt(long)]
+        point_on_surface: bool,
+        #[structopt(long)]
+        keep_tags: Option<String>,
+        #[structopt(long)]
+        drop_tags: Option<String>,
+        #[structopt(long)]
+        rename_tags: Option<String>,
+        #[structopt(long)]
+        ids: Option<String>,
+        #[structopt(long, parse(from_os_str))]
+        ids_file: Option<std::path::PathBuf>,
+        #[structopt(long, conflicts_with_all = &["boundary", "polygon"])]
+        bbox: Option<Bounds>,
+        #[structopt(long, conflicts_with = "polygon")]
+        boundary: Option<String>,
+        #[structopt(long, parse(from_os_str))]
+        polygon: Option<std::path::PathBuf>,
+        #[structopt(long, conflicts_with = "centroid")]
+        within: bool,
+        #[structopt(long)]
+        centroid: bool,
+        #[structopt(short, long)]
+        geojson: bool,
+        #[structopt(long, parse(from_os_str))]
+        node_store: Option<std::path::PathBuf>,
     },
     Streets {
         #[structopt(short, long)]
@@ -30,6 +69,16 @@ enum Command {
         name: Option<String>,
         #[structopt(short, long)]
         boundary: Option<u8>,
+        #[structopt(long, parse(from_os_str))]
+        node_store: Option<std::path::PathBuf>,
+    },
+    Addresses {
+        #[structopt(short, long)]
+        geojson: bool,
+        #[structopt(long)]
+        bbox: Option<Bounds>,
+        #[structopt(long, parse(from_os_str))]
+        node_store: Option<std::path::PathBuf>,
     },
     Boundaries {
         #[structopt(short, long)]
@@ -49,28 +98,150 @@ fn main() -> Result<(), Box<dyn Error>> {
     match args.cmd {
         Command::Objects {
             tags,
+            filter_file,
+            profile,
             retain_coordinates,
+            normalize_values,
+            report_groups,
+            retain_refs,
+            measures,
+            point_on_surface,
+            keep_tags,
+            drop_tags,
+            rename_tags,
+            ids,
+            ids_file,
+            bbox,
+            boundary,
+            polygon,
+            within,
+            centroid,
+            geojson,
+            node_store,
         } => {
-            let objects = if let Some(tags) = tags {
-                let groups = filter::parse(&tags);
-                objects(file, Some(&groups), retain_coordinates)?
+            let predicate = if within {
+                SpatialPredicate::Within
+            } else if centroid {
+                SpatialPredicate::Centroid
             } else {
-                objects(file, None, retain_coordinates)?
+                SpatialPredicate::Intersects
+            };
+            let area = match (bbox, boundary, polygon) {
+                (Some(bounds), _, _) => Some(Area::Bounds(bounds)),
+                (_, Some(name), _) => Some(Area::Boundary(name)),
+                (_, _, Some(path)) => Some(Area::from_geojson(&fs::read_to_string(path)?)?),
+                _ => None,
             };
-            objects.write_json_lines(&mut handle)?;
+            let area = area.as_ref().map(|area| (area, predicate));
+            let mut groups: Option<Vec<LabeledGroup>> = match tags {
+                Some(tags) => Some(filter::parse_labeled(&tags)?),
+                None => None,
+            };
+            if let Some(path) = filter_file {
+                let content = fs::read_to_string(&path)?;
+                let profiles = match path.extension().and_then(|ext| ext.to_str()) {
+                    Some("json") => Profiles::from_json(&content)?,
+                    _ => Profiles::from_toml(&content)?,
+                };
+                let names: Vec<&str> = profile.iter().map(String::as_str).collect();
+                let mut profile_groups = profiles.groups(&names)?;
+                groups = match groups {
+                    Some(mut groups) => {
+                        groups.append(&mut profile_groups);
+                        Some(groups)
+                    }
+                    None => Some(profile_groups),
+                };
+            }
+            if normalize_values {
+                groups = groups.map(|groups| {
+                    groups
+                        .into_iter()
+                        .map(|(label, group)| (label, group.normalized()))
+                        .collect()
+                });
+            }
+            if ids.is_some() || ids_file.is_some() {
+                let mut id_set = filter::parse_ids(ids.as_deref().unwrap_or(""))?;
+                if let Some(path) = ids_file {
+                    id_set.extend(filter::parse_ids(&fs::read_to_string(path)?)?);
+                }
+                let condition = Condition::Ids(id_set);
+                groups = match groups {
+                    Some(groups) => Some(
+                        groups
+                            .into_iter()
+                            .map(|(label, mut group)| {
+                                group.conditions.insert(0, condition.clone());
+                                (label, group)
+                            })
+                            .collect(),
+                    ),
+                    None => Some(vec![(
+                        None,
+                        Group {
+                            conditions: vec![condition],
+                        },
+                    )]),
+                };
+            }
+            let tags = TagProjection {
+                keep: filter::parse_keys(keep_tags.as_deref().unwrap_or(""))?,
+                drop: filter::parse_keys(drop_tags.as_deref().unwrap_or(""))?,
+                rename: filter::parse_renames(rename_tags.as_deref().unwrap_or(""))?,
+            };
+            let labels: Vec<_> = groups
+                .iter()
+                .flatten()
+                .map(|(label, _)| label.clone())
+                .collect();
+            let groups: Option<Vec<Group>> =
+                groups.map(|groups| groups.into_iter().map(|(_, group)| group).collect());
+            let options = ObjectOptions {
+                groups: groups.as_deref(),
+                retain_coordinates,
+                area,
+                report_groups,
+                labels: &labels,
+                retain_refs,
+                measures,
+                point_on_surface,
+                tags,
+                node_store: node_store.as_deref(),
+            };
+            let mut writer = ObjectWriter::new(&mut handle, geojson);
+            stream_objects(file, &options, |object| writer.write(&object))?;
+            writer.finish()?;
         }
         Command::Streets {
             geojson,
             name,
             boundary,
+            node_store,
         } => {
-            let streets = streets(file, name.as_deref(), boundary)?;
+            let streets = streets(file, name.as_deref(), boundary, node_store.as_deref())?;
             if geojson {
                 streets.write_geojson(&mut handle)?;
             } else {
                 streets.write_json_lines(&mut handle)?;
             }
         }
+        Command::Addresses {
+            geojson,
+            bbox,
+            node_store,
+        } => {
+            let area = bbox.map(Area::Bounds);
+            let area = area
+                .as_ref()
+                .map(|area| (area, SpatialPredicate::Intersects));
+            let addresses = addresses(file, area, node_store.as_deref())?;
+            if geojson {
+                addresses.write_geojson(&mut handle)?;
+            } else {
+                addresses.write_json_lines(&mut handle)?;
+            }
+        }
         Command::Boundaries { levels, geojson } => {
             let boundaries = boundaries(file, levels)?;
             if geojson {
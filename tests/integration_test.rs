@@ -1,8 +1,13 @@
 extern crate osm_pbf2json;
 
+use geo_types::Geometry;
 use geojson::GeoJson;
-use osm_pbf2json::output::Output;
-use osm_pbf2json::{boundaries, filter, objects, streets};
+use osm_pbf2json::filter::{Condition, Group, TagProjection};
+use osm_pbf2json::items::osm::{Area, GeoInfo, GroupRef, Object, SpatialPredicate};
+use osm_pbf2json::output::{ObjectWriter, Output};
+use osm_pbf2json::{
+    addresses, boundaries, filter, objects, stream_objects, streets, ObjectOptions,
+};
 use std::fs::File;
 use std::io::{Cursor, Read, Seek, SeekFrom};
 
@@ -13,12 +18,31 @@ fn get_string(cursor: &mut Cursor<Vec<u8>>) -> String {
     String::from_utf8(out).unwrap()
 }
 
+const ALEXANDERPLATZ: &str = "./tests/data/alexanderplatz.pbf";
+const WILHELMSTRASSE: &str = "./tests/data/wilhelmstrasse.pbf";
+
+fn extract_objects(path: &str, options: &ObjectOptions) -> Vec<Object> {
+    let file = File::open(path).unwrap();
+    objects(file, options).unwrap()
+}
+
+fn count_objects(groups: &[Group]) -> usize {
+    let options = ObjectOptions {
+        groups: Some(groups),
+        ..Default::default()
+    };
+    extract_objects(ALEXANDERPLATZ, &options).len()
+}
+
 #[test]
 fn find_fountains_or_townhalls() {
     let mut cursor = Cursor::new(Vec::new());
-    let groups = filter::parse("amenity~fountain+tourism,amenity~townhall");
-    let file = File::open("./tests/data/alexanderplatz.pbf").unwrap();
-    let objects = objects(file, Some(&groups), false).unwrap();
+    let groups = filter::parse("amenity~fountain+tourism,amenity~townhall").unwrap();
+    let options = ObjectOptions {
+        groups: Some(&groups),
+        ..Default::default()
+    };
+    let objects = extract_objects(ALEXANDERPLATZ, &options);
     objects.write_json_lines(&mut cursor).unwrap();
     let string = get_string(&mut cursor);
     let lines: Vec<&str> = string.trim().split('\n').collect();
@@ -34,20 +58,45 @@ fn find_fountains_or_townhalls() {
 #[test]
 fn find_bike_parking_for_six() {
     let mut cursor = Cursor::new(Vec::new());
-    let groups = filter::parse("amenity~bicycle_parking+capacity~6");
-    let file = File::open("./tests/data/alexanderplatz.pbf").unwrap();
-    let objects = objects(file, Some(&groups), false).unwrap();
+    let groups = filter::parse("amenity~bicycle_parking+capacity~6").unwrap();
+    let options = ObjectOptions {
+        groups: Some(&groups),
+        ..Default::default()
+    };
+    let objects = extract_objects(ALEXANDERPLATZ, &options);
     objects.write_json_lines(&mut cursor).unwrap();
     let string = get_string(&mut cursor);
     let lines = string.trim().split('\n');
     assert_eq!(lines.count(), 14);
 }
 
+#[test]
+fn find_fountains_in_bbox() {
+    let groups = filter::parse("amenity~fountain").unwrap();
+    let bounds = Area::Bounds("13.4068,52.5195,13.41,52.525".parse().unwrap());
+    let bbox = Some((&bounds, SpatialPredicate::Intersects));
+    let options = ObjectOptions {
+        groups: Some(&groups),
+        area: bbox,
+        ..Default::default()
+    };
+    let objects_intersecting = extract_objects(ALEXANDERPLATZ, &options);
+    assert_eq!(objects_intersecting.len(), 3);
+    let bbox = Some((&bounds, SpatialPredicate::Within));
+    let options = ObjectOptions {
+        groups: Some(&groups),
+        area: bbox,
+        ..Default::default()
+    };
+    let objects_within = extract_objects(ALEXANDERPLATZ, &options);
+    assert_eq!(objects_within.len(), 2);
+}
+
 #[test]
 fn streets_as_geojson() {
     let mut cursor = Cursor::new(Vec::new());
-    let file = File::open("./tests/data/alexanderplatz.pbf").unwrap();
-    let streets = streets(file, Some("Alexanderstraße"), None).unwrap();
+    let file = File::open(ALEXANDERPLATZ).unwrap();
+    let streets = streets(file, Some("Alexanderstraße"), None, None).unwrap();
     streets.write_geojson(&mut cursor).unwrap();
     let geojson_str = get_string(&mut cursor);
     let geojson = geojson_str.parse::<GeoJson>().unwrap();
@@ -62,8 +111,8 @@ fn streets_as_geojson() {
 fn rosa_luxemburg_street() {
     let mut cursor = Cursor::new(Vec::new());
     let name = "Rosa-Luxemburg-Straße";
-    let file = File::open("./tests/data/alexanderplatz.pbf").unwrap();
-    let streets = streets(file, Some(name), None).unwrap();
+    let file = File::open(ALEXANDERPLATZ).unwrap();
+    let streets = streets(file, Some(name), None, None).unwrap();
     streets.write_json_lines(&mut cursor).unwrap();
     let string = get_string(&mut cursor);
     let lines: Vec<&str> = string.trim().split('\n').collect();
@@ -75,8 +124,8 @@ fn rosa_luxemburg_street() {
 fn split_street_by_boundary() {
     let mut cursor = Cursor::new(Vec::new());
     let name = "Wilhelmstraße";
-    let file = File::open("./tests/data/wilhelmstrasse.pbf").unwrap();
-    let streets = streets(file, Some(name), Some(10)).unwrap();
+    let file = File::open(WILHELMSTRASSE).unwrap();
+    let streets = streets(file, Some(name), Some(10), None).unwrap();
     streets.write_json_lines(&mut cursor).unwrap();
     let string = get_string(&mut cursor);
     let mut lines: Vec<&str> = string.trim().split('\n').collect();
@@ -91,7 +140,7 @@ fn split_street_by_boundary() {
 #[test]
 fn boundary_as_geojson() {
     let mut cursor = Cursor::new(Vec::new());
-    let file = File::open("./tests/data/wilhelmstrasse.pbf").unwrap();
+    let file = File::open(WILHELMSTRASSE).unwrap();
     let boundaries = boundaries(file, Some(vec![10])).unwrap();
     boundaries.write_geojson(&mut cursor).unwrap();
     let geojson_str = get_string(&mut cursor);
@@ -106,7 +155,7 @@ fn boundary_as_geojson() {
 #[test]
 fn extract_boundaries() {
     let mut cursor = Cursor::new(Vec::new());
-    let file = File::open("./tests/data/wilhelmstrasse.pbf").unwrap();
+    let file = File::open(WILHELMSTRASSE).unwrap();
     let boundaries = boundaries(file, Some(vec![10])).unwrap();
     boundaries.write_json_lines(&mut cursor).unwrap();
     let string = get_string(&mut cursor);
@@ -116,3 +165,549 @@ fn extract_boundaries() {
     assert!(lines[0].contains("Kreuzberg"));
     assert!(lines[1].contains("Mitte"));
 }
+
+fn count_highways(area: &Area, predicate: SpatialPredicate) -> usize {
+    let groups = filter::parse("highway").unwrap();
+    let options = ObjectOptions {
+        groups: Some(&groups),
+        area: Some((area, predicate)),
+        ..Default::default()
+    };
+    extract_objects(WILHELMSTRASSE, &options).len()
+}
+
+#[test]
+fn find_highways_in_boundary() {
+    let mitte = Area::Boundary("Mitte".into());
+    assert_eq!(count_highways(&mitte, SpatialPredicate::Intersects), 49);
+    assert_eq!(count_highways(&mitte, SpatialPredicate::Within), 48);
+    let kreuzberg = Area::Boundary("Kreuzberg".into());
+    assert_eq!(count_highways(&kreuzberg, SpatialPredicate::Centroid), 30);
+    assert_eq!(count_highways(&kreuzberg, SpatialPredicate::Within), 29);
+}
+
+#[test]
+fn find_highways_in_polygon() {
+    // the southern part of the extract, crossed by a street at 52.51°N
+    let json = r#"{
+        "type": "Polygon",
+        "coordinates": [[[13.3, 52.4], [13.5, 52.4], [13.5, 52.51], [13.3, 52.51], [13.3, 52.4]]]
+    }"#;
+    let polygon = Area::from_geojson(json).unwrap();
+    assert_eq!(count_highways(&polygon, SpatialPredicate::Intersects), 39);
+    assert_eq!(count_highways(&polygon, SpatialPredicate::Within), 38);
+}
+
+#[test]
+fn find_objects_by_id() {
+    let ids = filter::parse_ids("n440937810,w23813204").unwrap();
+    let group = Group {
+        conditions: vec![Condition::Ids(ids)],
+    };
+    let options = ObjectOptions {
+        groups: Some(&[group.clone()]),
+        ..Default::default()
+    };
+    let objects_by_id = extract_objects(ALEXANDERPLATZ, &options);
+    assert_eq!(objects_by_id.len(), 2);
+
+    let mut group = group;
+    group
+        .conditions
+        .push(Condition::new("name", Some("Neptunbrunnen")));
+    let groups = vec![group];
+    let options = ObjectOptions {
+        groups: Some(&groups),
+        ..Default::default()
+    };
+    let objects_by_id = extract_objects(ALEXANDERPLATZ, &options);
+    assert_eq!(objects_by_id.len(), 1);
+
+    // reading stops early, but the geometry is resolved as without ids
+    let ids = filter::parse_ids("r131761").unwrap();
+    let groups = vec![Group {
+        conditions: vec![Condition::Ids(ids)],
+    }];
+    let options = ObjectOptions {
+        groups: Some(&groups),
+        ..Default::default()
+    };
+    let by_id = extract_objects(ALEXANDERPLATZ, &options);
+    let groups = filter::parse("relation:highway~pedestrian").unwrap();
+    let options = ObjectOptions {
+        groups: Some(&groups),
+        ..Default::default()
+    };
+    let by_tags = extract_objects(ALEXANDERPLATZ, &options);
+    let relation = by_tags.iter().find(|o| o.id() == 131761).unwrap();
+    assert_eq!(by_id.len(), 1);
+    assert_eq!(by_id[0].geometry(), relation.geometry());
+}
+
+#[test]
+fn find_route_members() {
+    let count = |expression| count_objects(&filter::parse(expression).unwrap());
+    assert_eq!(count("way:member(type~route+route~bus)"), 167);
+    assert_eq!(count("node:member[stop](type~route+route~bus)"), 23);
+    assert_eq!(count("node:member[stop](type~route)"), 45);
+}
+
+#[test]
+fn find_by_geometry() {
+    let count = |expression| count_objects(&filter::parse(expression).unwrap());
+    assert_eq!(count("building+@area>=1000"), 62);
+    assert_eq!(count("building+!@area>=1000"), 257);
+    assert_eq!(count("highway~footway+@length<10"), 188);
+    // other relations are not measured on the convex hull of their members
+    assert_eq!(count("relation:type~route"), 124);
+    assert_eq!(count("relation:type~route+@area>=1"), 0);
+}
+
+#[test]
+fn report_matched_groups() {
+    let expression = "[footways]highway~footway,building,highway+@length<10";
+    let (labels, groups): (Vec<_>, Vec<_>) = filter::parse_labeled(expression)
+        .unwrap()
+        .into_iter()
+        .unzip();
+    let options = ObjectOptions {
+        groups: Some(&groups),
+        report_groups: true,
+        labels: &labels,
+        ..Default::default()
+    };
+    let objects = extract_objects(ALEXANDERPLATZ, &options);
+    assert_eq!(objects.len(), 927);
+
+    let count = |refs: &[GroupRef]| {
+        objects
+            .iter()
+            .filter(|object| object.groups() == Some(refs))
+            .count()
+    };
+    let footways = GroupRef::Label("footways".into());
+    assert_eq!(count(&[footways.clone()]), 318);
+    assert_eq!(count(&[footways, GroupRef::Index(2)]), 188);
+    assert_eq!(count(&[GroupRef::Index(1)]), 319);
+    assert_eq!(count(&[GroupRef::Index(2)]), 102);
+
+    let expression = "[paths]highway~footway,[paths]highway~footway|steps";
+    let (labels, groups): (Vec<_>, Vec<_>) = filter::parse_labeled(expression)
+        .unwrap()
+        .into_iter()
+        .unzip();
+    let options = ObjectOptions {
+        groups: Some(&groups),
+        report_groups: true,
+        labels: &labels,
+        ..Default::default()
+    };
+    let objects = extract_objects(ALEXANDERPLATZ, &options);
+    let paths = [GroupRef::Label("paths".into())];
+    assert!(!objects.is_empty());
+    assert!(objects
+        .iter()
+        .all(|object| object.groups() == Some(&paths[..])));
+}
+
+#[test]
+fn find_by_profile() {
+    let profiles = filter::Profiles::from_toml(
+        r#"
+[buildings]
+expression = "building+@area>=1000"
+
+[[footways.groups]]
+conditions = [{ value_match = ["highway", "footway"] }, { geometry = ["length", "<", 10] }]
+"#,
+    )
+    .unwrap();
+    let count = |names: &[&str]| {
+        let groups = profiles.groups(names).unwrap();
+        let groups: Vec<Group> = groups.into_iter().map(|(_, group)| group).collect();
+        count_objects(&groups)
+    };
+    assert_eq!(count(&["buildings"]), 62);
+    assert_eq!(count(&["footways"]), 188);
+    assert_eq!(count(&[]), 250);
+}
+
+#[test]
+fn find_multipolygons_by_area() {
+    let count = |expression| count_objects(&filter::parse(expression).unwrap());
+    // the convex hulls of 3 more relations would exceed 10,000 m², but multipolygons falling
+    // back to them are not measured
+    assert_eq!(count("relation:type~multipolygon"), 29);
+    assert_eq!(count("relation:type~multipolygon+@area>=10000"), 4);
+    assert_eq!(count("relation:type~multipolygon+@area<1000"), 4);
+}
+
+#[test]
+fn relation_geometries() {
+    let groups = filter::parse("relation:type~multipolygon,relation:type~route").unwrap();
+    let options = ObjectOptions {
+        groups: Some(&groups),
+        report_groups: true,
+        ..Default::default()
+    };
+    let objects = extract_objects(ALEXANDERPLATZ, &options);
+    let count = |index: usize, geometry_type: fn(&Geometry<f64>) -> bool| {
+        objects
+            .iter()
+            .filter(|o| o.groups() == Some(&[GroupRef::Index(index)]))
+            .filter(|o| o.geometry().is_some_and(geometry_type))
+            .count()
+    };
+    assert_eq!(objects.len(), 153);
+    // multipolygons with members missing from the extract fall back to their member geometries
+    assert_eq!(count(0, |g| matches!(g, Geometry::MultiPolygon(_))), 26);
+    assert_eq!(
+        count(0, |g| matches!(g, Geometry::GeometryCollection(_))),
+        3
+    );
+    assert_eq!(
+        count(1, |g| matches!(g, Geometry::GeometryCollection(_))),
+        124
+    );
+}
+
+#[test]
+fn stream_objects_in_order() {
+    let groups = filter::parse("highway~pedestrian").unwrap();
+    let file = File::open(ALEXANDERPLATZ).unwrap();
+    let mut types = vec![];
+    stream_objects(
+        file,
+        &ObjectOptions {
+            groups: Some(&groups),
+            ..Default::default()
+        },
+        |object| {
+            types.push(object.osm_type());
+            Ok(())
+        },
+    )
+    .unwrap();
+    let options = ObjectOptions {
+        groups: Some(&groups),
+        ..Default::default()
+    };
+    let objects = extract_objects(ALEXANDERPLATZ, &options);
+    assert_eq!(types.len(), objects.len());
+    let mut sorted = types.clone();
+    sorted.sort_by_key(|osm_type| {
+        ["node", "way", "relation"]
+            .iter()
+            .position(|t| t == osm_type)
+    });
+    assert_eq!(types, sorted);
+    assert!(types.contains(&"relation"));
+
+    let file = File::open(ALEXANDERPLATZ).unwrap();
+    let mut count = 0;
+    let result = stream_objects(
+        file,
+        &ObjectOptions {
+            groups: Some(&groups),
+            ..Default::default()
+        },
+        |_| {
+            count += 1;
+            Err("stop".into())
+        },
+    );
+    assert!(result.is_err());
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn write_streamed_objects() {
+    let groups = filter::parse("amenity~bench").unwrap();
+    let options = ObjectOptions {
+        groups: Some(&groups),
+        ..Default::default()
+    };
+    let objects = extract_objects(ALEXANDERPLATZ, &options);
+    let parse = |output: Vec<u8>| -> Vec<serde_json::Value> {
+        let output = String::from_utf8(output).unwrap();
+        output.lines().map(|line| line.parse().unwrap()).collect()
+    };
+    for &geojson in [false, true].iter() {
+        let mut written = vec![];
+        if geojson {
+            objects.write_geojson(&mut written).unwrap();
+        } else {
+            objects.write_json_lines(&mut written).unwrap();
+        }
+        let mut streamed = vec![];
+        let mut writer = ObjectWriter::new(&mut streamed, geojson);
+        for object in objects.iter() {
+            writer.write(object).unwrap();
+        }
+        writer.finish().unwrap();
+        assert_eq!(parse(streamed), parse(written));
+    }
+}
+
+#[test]
+fn write_objects_without_geometry() {
+    let geo_info = GeoInfo::Point {
+        lon: 13.4,
+        lat: 52.5,
+    };
+    let tags = osmpbfreader::objects::Tags::new();
+    let objects = vec![Object::new(1, "relation", tags, geo_info)];
+    let mut written = vec![];
+    objects.write_geojson(&mut written).unwrap();
+    let collection: serde_json::Value = serde_json::from_slice(&written).unwrap();
+    assert_eq!(
+        collection["features"][0]["geometry"],
+        serde_json::Value::Null
+    );
+    assert_eq!(collection["features"][0]["properties"]["@id"], "1");
+}
+
+#[test]
+fn resolve_locations_from_node_store() {
+    let groups = filter::parse("highway~pedestrian,building~yes").unwrap();
+    let file_name = format!("osm_pbf2json_node_store_{}", std::process::id());
+    let path = std::env::temp_dir().join(file_name);
+    let _ = std::fs::remove_file(&path);
+    let options = ObjectOptions {
+        groups: Some(&groups),
+        ..Default::default()
+    };
+    let in_memory = extract_objects(ALEXANDERPLATZ, &options);
+    let options = ObjectOptions {
+        groups: Some(&groups),
+        node_store: Some(&path),
+        ..Default::default()
+    };
+    let stored = extract_objects(ALEXANDERPLATZ, &options);
+    assert!(!path.exists());
+    assert_eq!(in_memory.len(), stored.len());
+    for (a, b) in in_memory.iter().zip(stored.iter()) {
+        assert_eq!(a.id(), b.id());
+        assert_eq!(a.geometry(), b.geometry());
+    }
+
+    let file = File::open(ALEXANDERPLATZ).unwrap();
+    let in_memory = streets(file, None, None, None).unwrap();
+    let file = File::open(ALEXANDERPLATZ).unwrap();
+    let stored = streets(file, None, None, Some(&path)).unwrap();
+    assert!(!stored.is_empty());
+    // streets are not returned in a stable order
+    let segments = |streets: &[osm_pbf2json::items::Street]| {
+        let mut segments: Vec<String> = streets
+            .iter()
+            .flat_map(|street| street.segments.iter())
+            .map(|segment| format!("{:?}", segment))
+            .collect();
+        segments.sort();
+        segments
+    };
+    assert_eq!(segments(&in_memory), segments(&stored));
+}
+
+#[test]
+fn retain_members_and_nodes() {
+    let groups = filter::parse("highway~pedestrian").unwrap();
+    let options = ObjectOptions {
+        groups: Some(&groups),
+        retain_refs: true,
+        ..Default::default()
+    };
+    let retained = extract_objects(ALEXANDERPLATZ, &options);
+    for object in retained.iter() {
+        match object.osm_type() {
+            "way" => assert!(object.nodes().is_some_and(|nodes| nodes.len() >= 2)),
+            "relation" => assert!(object.members().is_some_and(|m| !m.is_empty())),
+            _ => assert!(object.nodes().is_none() && object.members().is_none()),
+        }
+    }
+    let relation = retained
+        .iter()
+        .find(|o| o.osm_type() == "relation")
+        .unwrap();
+    let members = relation.members().unwrap();
+    assert_eq!(relation.id(), 131761);
+    assert_eq!(members.len(), 18);
+    assert_eq!((members[0].osm_type, members[0].id), ("way", 61579353));
+
+    let options = ObjectOptions {
+        groups: Some(&groups),
+        ..Default::default()
+    };
+    let objects = extract_objects(ALEXANDERPLATZ, &options);
+    assert!(objects
+        .iter()
+        .all(|o| o.nodes().is_none() && o.members().is_none()));
+}
+
+#[test]
+fn points_on_surface_of_areas() {
+    use geo::prelude::Contains;
+    use geo_types::Point;
+
+    let groups = filter::parse("building,highway~pedestrian").unwrap();
+    let options = ObjectOptions {
+        groups: Some(&groups),
+        ..Default::default()
+    };
+    let objects = extract_objects(ALEXANDERPLATZ, &options);
+    assert!(objects.iter().all(|o| match o.geo_info() {
+        GeoInfo::Shape {
+            point_on_surface, ..
+        } => point_on_surface.is_none(),
+        GeoInfo::Point { .. } => true,
+    }));
+
+    let options = ObjectOptions {
+        groups: Some(&groups),
+        point_on_surface: true,
+        ..Default::default()
+    };
+    let objects = extract_objects(ALEXANDERPLATZ, &options);
+    let mut areas = 0;
+    for object in objects.iter() {
+        let point = match object.geo_info() {
+            GeoInfo::Shape {
+                point_on_surface: Some(point),
+                ..
+            } => Point::new(point.lon, point.lat),
+            _ => continue,
+        };
+        let inside = match object.geometry() {
+            Some(Geometry::Polygon(polygon)) => polygon.contains(&point),
+            Some(Geometry::MultiPolygon(multi_polygon)) => multi_polygon.contains(&point),
+            _ => false,
+        };
+        assert!(inside, "{} {}", object.osm_type(), object.id());
+        areas += 1;
+    }
+    assert_eq!(areas, 295);
+    // a closed way without area, A → B → A
+    let degenerate = objects.iter().find(|o| o.id() == 315548172).unwrap();
+    assert!(matches!(
+        degenerate.geo_info(),
+        GeoInfo::Shape {
+            point_on_surface: None,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn measure_areas_and_lengths() {
+    let groups = filter::parse("building,highway~pedestrian").unwrap();
+    let options = ObjectOptions {
+        groups: Some(&groups),
+        measures: true,
+        ..Default::default()
+    };
+    let measured = extract_objects(ALEXANDERPLATZ, &options);
+    let mut counts = (0, 0);
+    for object in measured.iter() {
+        let (area, length) = match object.geo_info() {
+            GeoInfo::Shape {
+                area_m2, length_m, ..
+            } => (*area_m2, *length_m),
+            GeoInfo::Point { .. } => (None, None),
+        };
+        match object.geometry() {
+            Some(Geometry::Polygon(_)) | Some(Geometry::MultiPolygon(_)) => {
+                // ways clipped at the border of the extract can degenerate to no area
+                assert!(area.unwrap() >= 0. && length.is_none());
+                counts.0 += 1;
+            }
+            Some(Geometry::LineString(_)) => {
+                assert!(length.unwrap() > 0. && area.is_none());
+                counts.1 += 1;
+            }
+            _ => assert!(area.is_none() && length.is_none()),
+        }
+    }
+    assert_eq!(counts, (302, 78));
+    let area = |id| {
+        let object = measured.iter().find(|o| o.id() == id).unwrap();
+        match object.geo_info() {
+            GeoInfo::Shape { area_m2, .. } => area_m2.unwrap(),
+            GeoInfo::Point { .. } => panic!("not a shape"),
+        }
+    };
+    assert!((area(625034881) - 149.2).abs() < 0.1);
+    assert!((area(131761) - 53571.1).abs() < 0.1);
+
+    let options = ObjectOptions {
+        groups: Some(&groups),
+        ..Default::default()
+    };
+    let objects = extract_objects(ALEXANDERPLATZ, &options);
+    assert!(objects.iter().all(|o| match o.geo_info() {
+        GeoInfo::Shape {
+            area_m2, length_m, ..
+        } => area_m2.is_none() && length_m.is_none(),
+        GeoInfo::Point { .. } => true,
+    }));
+}
+
+#[test]
+fn project_tags() {
+    let groups = filter::parse("amenity~bench").unwrap();
+    let tags = TagProjection {
+        keep: filter::parse_keys("amenity,back*,material").unwrap(),
+        drop: filter::parse_keys("material").unwrap(),
+        rename: filter::parse_renames("amenity=category").unwrap(),
+    };
+    let options = ObjectOptions {
+        groups: Some(&groups),
+        tags,
+        ..Default::default()
+    };
+    let projected = extract_objects(ALEXANDERPLATZ, &options);
+    let options = ObjectOptions {
+        groups: Some(&groups),
+        ..Default::default()
+    };
+    let objects = extract_objects(ALEXANDERPLATZ, &options);
+    assert_eq!(projected.len(), objects.len());
+    for (object, original) in projected.iter().zip(objects.iter()) {
+        assert_eq!(object.id(), original.id());
+        let keys: Vec<&str> = object.tags().keys().map(|key| key.as_str()).collect();
+        let backrest = original.tags().get("backrest");
+        match backrest {
+            Some(_) => assert_eq!(keys, vec!["backrest", "category"]),
+            None => assert_eq!(keys, vec!["category"]),
+        }
+        assert_eq!(object.tags().get("backrest"), backrest);
+    }
+    let with_backrest = projected
+        .iter()
+        .filter(|o| o.tags().contains_key("backrest"));
+    assert_eq!(with_backrest.count(), 27);
+}
+
+#[test]
+fn extract_addresses() {
+    let file = File::open(ALEXANDERPLATZ).unwrap();
+    let all = addresses(file, None, None).unwrap();
+    assert_eq!(all.len(), 712);
+    let located = |address: &osm_pbf2json::items::Address| match &address.location {
+        Some(location) => location.lon > 13. && location.lat > 52.,
+        None => false,
+    };
+    assert!(all.iter().all(located));
+    let with_name = all.iter().filter(|address| address.housename.is_some());
+    assert_eq!(with_name.count(), 11);
+    let count = |osm_type| all.iter().filter(|a| a.osm_type == osm_type).count();
+    assert_eq!(
+        (count("node"), count("way"), count("relation")),
+        (672, 37, 3)
+    );
+
+    let bounds = Area::Bounds("13.4068,52.5195,13.41,52.525".parse().unwrap());
+    let file = File::open(ALEXANDERPLATZ).unwrap();
+    let area = Some((&bounds, SpatialPredicate::Intersects));
+    let within = addresses(file, area, None).unwrap();
+    assert_eq!(within.len(), 174);
+}